@@ -0,0 +1,88 @@
+use crate::reg;
+use core::{fmt, ptr, str};
+
+/// 96-bit factory-programmed unique ID: wafer X, and Y coordinates (16 bits each), wafer number, and 7 ASCII
+/// characters of the lot number, in this order
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Uid(pub [u8; 12]);
+
+/// Identification of the die, see `idcode`
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IdCode {
+	pub device: u16,  // 0x444 for STM32F03x
+	pub revision: u16,
+}
+
+/// `uid` as 24 upper-case hex digits, the same for a device across resets, and firmware updates
+///
+#[derive(Clone, Copy)]
+pub struct Serial([u8; 24]);
+
+impl Serial {
+	pub fn as_str(&self) -> &str {
+		unsafe {str::from_utf8_unchecked(&self.0)}  // Hex digits only
+	}
+}
+
+impl fmt::Display for Serial {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+pub fn uid() -> Uid {
+	let mut uid = [0; 12];
+
+	for (i, b) in uid.iter_mut().enumerate() {
+		*b = unsafe {ptr::read_volatile((reg::UID_BASE + i) as *const u8)};
+	}
+
+	Uid(uid)
+}
+
+/// Flash size, KiB
+///
+pub fn flash_size() -> usize {
+	unsafe {ptr::read_volatile(reg::FLASHSIZE_BASE as *const u16) as usize}
+}
+
+pub fn idcode() -> IdCode {
+	let idcode = unsafe {ptr::read_volatile((reg::DBGMCU_BASE + reg::DBGMCU_IDCODE_OFFSET) as *const usize)};
+
+	IdCode {
+		device: ((idcode & reg::DBGMCU_IDCODE_DEV_ID_MSK) >> reg::DBGMCU_IDCODE_DEV_ID_POS) as u16,
+		revision: ((idcode & reg::DBGMCU_IDCODE_REV_ID_MSK) >> reg::DBGMCU_IDCODE_REV_ID_POS) as u16,
+	}
+}
+
+pub fn serial() -> Serial {
+	const HEX: &[u8; 16] = b"0123456789ABCDEF";
+	let mut serial = [0; 24];
+
+	for (i, b) in uid().0.iter().enumerate() {
+		serial[2 * i] = HEX[(b >> 4) as usize];
+		serial[2 * i + 1] = HEX[(b & 0xf) as usize];
+	}
+
+	Serial(serial)
+}
+
+/// MAC-48 address derived from `uid`, locally administered, and unicast. The UID's halves are XOR-ed, so devices of
+/// a lot, which only differ in the wafer, and its coordinates, never collide. The first octet, which carries the
+/// flags, is folded from lot number characters only
+///
+pub fn mac() -> [u8; 6] {
+	let uid = uid().0;
+	let mut mac = [0; 6];
+
+	for (i, b) in mac.iter_mut().enumerate() {
+		let j = (i + 5) % 6;
+		*b = uid[j] ^ uid[j + 6];
+	}
+
+	mac[0] = (mac[0] | 0x02) & !0x01;  // Locally administered, unicast
+
+	mac
+}
@@ -16,18 +16,20 @@ extern "C" {
 	fn sv_call();
 	fn pend_sv();
 	fn sys_tick();
-	fn wwdg_irq();
-	fn tim14_irq();
+	fn irq_dispatch();  // See `periph::irq`
 }
 
+/// Serves system exceptions w/o a handler
+///
 #[export_name = "default_exception_handler"]
 pub fn default_exception_handler() -> ! {
+	crate::error!("Unhandled exception {}", crate::periph::irq::active_exception());
 	loop {}
 }
 
 #[link_section = ".vector_table.exceptions"]
 #[no_mangle]
-pub static EXCEPTIONS: [VectorEntry; 34] = [
+pub static EXCEPTIONS: [VectorEntry; 46] = [
 	VectorEntry {handler: nmi},
 	VectorEntry {handler: hard_fault_trampoline},
 	VectorEntry {reserved: 0},
@@ -42,26 +44,39 @@ pub static EXCEPTIONS: [VectorEntry; 34] = [
 	VectorEntry {reserved: 0},
 	VectorEntry {handler: pend_sv},
 	VectorEntry {handler: sys_tick},
-	VectorEntry {handler: wwdg_irq},  // WWDG
-	VectorEntry {reserved: 0},  // Reserved
-	VectorEntry {reserved: 0},  // RTC
-	VectorEntry {reserved: 0},  // FLASH
-	VectorEntry {reserved: 0},  // RCC
-	VectorEntry {reserved: 0},  // EXTI0_1
-	VectorEntry {reserved: 0},  // EXTI2_3
-	VectorEntry {reserved: 0},  // EXTI4_15
-	VectorEntry {reserved: 0},  // Reserved
-	VectorEntry {reserved: 0},  // DMA_CH1
-	VectorEntry {reserved: 0},  // DMA_CH2_3
-	VectorEntry {reserved: 0},  // DMA_CH4_5
-	VectorEntry {reserved: 0},  // ADC
-	VectorEntry {reserved: 0},  // TIM1_BRK_UP_TRG_COM
-	VectorEntry {reserved: 0},  // TIM1_CC
-	VectorEntry {reserved: 0},  // Reserved
-	VectorEntry {reserved: 0},  // TIM3
-	VectorEntry {reserved: 0},  //
-	VectorEntry {reserved: 0},  // Reserved
-	VectorEntry {handler: tim14_irq},  // TIM14
+	// IRQs are bound at runtime through `periph::irq::register`
+	VectorEntry {handler: irq_dispatch},  // WWDG
+	VectorEntry {handler: irq_dispatch},  // Reserved
+	VectorEntry {handler: irq_dispatch},  // RTC
+	VectorEntry {handler: irq_dispatch},  // FLASH
+	VectorEntry {handler: irq_dispatch},  // RCC
+	VectorEntry {handler: irq_dispatch},  // EXTI0_1
+	VectorEntry {handler: irq_dispatch},  // EXTI2_3
+	VectorEntry {handler: irq_dispatch},  // EXTI4_15
+	VectorEntry {handler: irq_dispatch},  // Reserved
+	VectorEntry {handler: irq_dispatch},  // DMA_CH1
+	VectorEntry {handler: irq_dispatch},  // DMA_CH2_3
+	VectorEntry {handler: irq_dispatch},  // DMA_CH4_5
+	VectorEntry {handler: irq_dispatch},  // ADC
+	VectorEntry {handler: irq_dispatch},  // TIM1_BRK_UP_TRG_COM
+	VectorEntry {handler: irq_dispatch},  // TIM1_CC
+	VectorEntry {handler: irq_dispatch},  // Reserved
+	VectorEntry {handler: irq_dispatch},  // TIM3
+	VectorEntry {handler: irq_dispatch},  // TIM6
+	VectorEntry {handler: irq_dispatch},  // Reserved
+	VectorEntry {handler: irq_dispatch},  // TIM14
+	VectorEntry {handler: irq_dispatch},  // TIM15
+	VectorEntry {handler: irq_dispatch},  // TIM16
+	VectorEntry {handler: irq_dispatch},  // TIM17
+	VectorEntry {handler: irq_dispatch},  // I2C1
+	VectorEntry {handler: irq_dispatch},  // I2C2
+	VectorEntry {handler: irq_dispatch},  // SPI1
+	VectorEntry {handler: irq_dispatch},  // SPI2
+	VectorEntry {handler: irq_dispatch},  // USART1
+	VectorEntry {handler: irq_dispatch},  // USART2
+	VectorEntry {handler: irq_dispatch},  // USART3_4_5_6
+	VectorEntry {handler: irq_dispatch},  // Reserved
+	VectorEntry {handler: irq_dispatch},  // Reserved
 ];
 
 #[panic_handler]
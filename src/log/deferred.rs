@@ -0,0 +1,153 @@
+use super::{Level, enabled, write_locked};
+use crate::{periph::systick, thread::task};
+
+#[cfg(feature = "log-uart")]
+use super::uart::write as write_bytes;
+#[cfg(feature = "log-semihosting")]
+use super::semihosting::write as write_bytes;
+#[cfg(feature = "log-rtt")]
+use super::rtt::write as write_bytes;
+
+const FRAME_HEADER: [u8; 2] = [0xa5, 0x5a];
+const FRAME_END: u8 = 0x00;
+const TASK_NONE: u8 = 0xff;
+
+/// Argument type tags. Warning: must be synchronized with `tools/logdecode`
+///
+#[repr(u8)]
+enum Tag {
+	U32 = 1,  // u32 value
+	I32,  // i32 value
+	U64,  // u64 value
+	I64,  // i64 value
+	Bool,  // u8 value
+	Char,  // u32 value
+	Str,  // u8 length, followed by bytes, truncated to 255 bytes
+}
+
+/// Types that may be passed as arguments into a deferred log record
+///
+pub trait Encode {
+	fn encode(&self);
+}
+
+fn encode_tagged(tag: Tag, bytes: &[u8]) {
+	write_bytes(&[tag as u8]);
+	write_bytes(bytes);
+}
+
+macro_rules! encode_impl {
+	($tag:ident, $as:ty, $($t:ty),+) => {
+		$(
+			impl Encode for $t {
+				fn encode(&self) {
+					encode_tagged(Tag::$tag, &(*self as $as).to_le_bytes());
+				}
+			}
+		)+
+	};
+}
+
+encode_impl!(U32, u32, u8, u16, u32, usize);
+encode_impl!(I32, i32, i8, i16, i32, isize);
+encode_impl!(U64, u64, u64);
+encode_impl!(I64, i64, i64);
+encode_impl!(Char, u32, char);
+
+impl Encode for bool {
+	fn encode(&self) {
+		encode_tagged(Tag::Bool, &[*self as u8]);
+	}
+}
+
+impl Encode for str {
+	fn encode(&self) {
+		let len = core::cmp::min(self.len(), u8::MAX as usize);
+		encode_tagged(Tag::Str, &[len as u8]);
+		write_bytes(&self.as_bytes()[..len]);
+	}
+}
+
+impl<T: ?Sized> Encode for *const T {
+	fn encode(&self) {
+		(self.cast::<u8>() as usize).encode();
+	}
+}
+
+impl<T: ?Sized> Encode for *mut T {
+	fn encode(&self) {
+		(self.cast::<u8>() as usize).encode();
+	}
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+	fn encode(&self) {
+		(**self).encode();
+	}
+}
+
+/// Produces a NUL-terminated copy of a format string to be stored in `.log_strings`
+///
+pub const fn intern<const N: usize>(format: &str) -> [u8; N] {
+	let bytes = format.as_bytes();
+	let mut ret = [0; N];
+	let mut i = 0;
+
+	while i < bytes.len() {
+		ret[i] = bytes[i];
+		i += 1;
+	}
+
+	ret
+}
+
+/// Sends a record frame.
+///
+/// Format strings are placed into `.log_strings` section which is not loaded into the device. A frame carries the
+/// string's address in that section and raw argument values. The text is reconstructed on the host by
+/// `tools/logdecode` which reads the strings from the ELF.
+///
+/// Frame layout (little endian):
+///
+/// | 0xa5 0x5a | level: u8 | tick: u32 | task: u8 (0xff - none) | string: u16 | args: (tag: u8, value)* | 0x00 |
+///
+/// Not to be used directly, see `error!`, `warn!`, `info!`, `debug!`, and `trace!`
+///
+pub fn record(level: Level, module: &str, string: usize, args: &[&dyn Encode]) {
+	if !enabled(level, module) {
+		return;
+	}
+
+	write_locked(|| {
+		let task = match task::current() {
+			Some(id) => id as u8,
+			None => TASK_NONE,
+		};
+
+		write_bytes(&FRAME_HEADER);
+		write_bytes(&[level as u8]);
+		write_bytes(&(systick::ticks() as u32).to_le_bytes());
+		write_bytes(&[task]);
+		write_bytes(&(string as u16).to_le_bytes());
+
+		for arg in args {
+			arg.encode();
+		}
+
+		write_bytes(&[FRAME_END]);
+	});
+}
+
+/// Deferred counterpart of `log_record!`. Only positional arguments implementing `log::deferred::Encode` are supported
+///
+#[macro_export]
+macro_rules! log_record {
+	($level:expr, $format:literal $(, $p:expr)* $(,)?) => {
+		if $level <= $crate::log::MAX_LEVEL {
+			#[link_section = ".log_strings"]
+			static STRING: [u8; $format.len() + 1] = $crate::log::deferred::intern($format);
+			$crate::log::deferred::record($level, module_path!(), core::ptr::addr_of!(STRING) as usize,
+				&[$(&$p as &dyn $crate::log::deferred::Encode),*]);
+		}
+	};
+}
@@ -0,0 +1,232 @@
+#[cfg(feature = "log-uart")] mod uart;
+#[cfg(feature = "log-semihosting")] mod semihosting;
+#[cfg(feature = "log-rtt")] pub mod rtt;
+#[cfg(feature = "log-deferred")] pub mod deferred;
+
+use crate::thread::{sync, sync::Lock};
+#[cfg(not(feature = "log-deferred"))]
+use crate::{periph::systick, thread::task};
+#[cfg(not(feature = "log-deferred"))]
+use core::{fmt, fmt::Write};
+
+#[cfg(any(
+	all(feature = "log-uart", feature = "log-semihosting"),
+	all(feature = "log-uart", feature = "log-rtt"),
+	all(feature = "log-semihosting", feature = "log-rtt"),
+))]
+compile_error!("Only one logger backend may be selected (`log-uart`, `log-semihosting`, `log-rtt`)");
+
+#[cfg(not(any(feature = "log-uart", feature = "log-semihosting", feature = "log-rtt")))]
+compile_error!("A logger backend must be selected (`log-uart`, `log-semihosting`, `log-rtt`)");
+
+#[cfg(feature = "log-uart")]
+pub use uart::UartLogger as Logger;
+#[cfg(feature = "log-semihosting")]
+pub use semihosting::SemihostingLogger as Logger;
+#[cfg(feature = "log-rtt")]
+pub use rtt::RttLogger as Logger;
+
+/// Log severity. The lower the value, the more important the message is
+///
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+#[repr(u8)]
+pub enum Level {
+	Off = 0,
+	Error,
+	Warn,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl Level {
+	#[cfg(not(feature = "log-deferred"))]
+	fn tag(&self) -> &'static str {
+		match self {
+			Level::Off => "",
+			Level::Error => "E",
+			Level::Warn => "W",
+			Level::Info => "I",
+			Level::Debug => "D",
+			Level::Trace => "T",
+		}
+	}
+}
+
+/// Records above this level are eliminated at compile time along w/ their format strings. Selected by
+/// `log-max-level-*` features, `Trace` if none is set
+///
+pub const MAX_LEVEL: Level = if cfg!(feature = "log-max-level-off") {
+	Level::Off
+} else if cfg!(feature = "log-max-level-error") {
+	Level::Error
+} else if cfg!(feature = "log-max-level-warn") {
+	Level::Warn
+} else if cfg!(feature = "log-max-level-info") {
+	Level::Info
+} else if cfg!(feature = "log-max-level-debug") {
+	Level::Debug
+} else {
+	Level::Trace
+};
+
+const MODULE_FILTERS_MAX: usize = 4;
+
+static mut LEVEL: Level = Level::Trace;
+static mut LOCK: sync::Sem = sync::Sem::new(1, 1);  // Serializes records coming from different tasks
+static mut MODULE_FILTERS: [Option<(&'static str, Level)>; MODULE_FILTERS_MAX] = [None; MODULE_FILTERS_MAX];
+
+/// Sets the runtime level applied to modules that do not have a filter of their own
+///
+pub fn set_level(level: Level) {
+	unsafe {
+		LEVEL = level;
+	}
+}
+
+pub fn level() -> Level {
+	unsafe {
+		LEVEL
+	}
+}
+
+/// Overrides the runtime level for a module and its submodules, e.g. `app::periph`. The longest matching
+/// prefix wins.
+///
+/// Returns false, if there are no free filter slots left
+///
+pub fn set_module_level(module: &'static str, level: Level) -> bool {
+	unsafe {
+		for filter in MODULE_FILTERS.iter_mut() {
+			match filter {
+				Some((m, l)) if *m == module => {
+					*l = level;
+					return true;
+				},
+				None => {
+					*filter = Some((module, level));
+					return true;
+				},
+				_ => {},
+			}
+		}
+	}
+
+	false
+}
+
+fn enabled(level: Level, module: &str) -> bool {
+	let mut threshold = unsafe {LEVEL};
+	let mut matched = 0;
+
+	unsafe {
+		for (m, l) in MODULE_FILTERS.iter().flatten() {
+			let is_prefix = module.starts_with(m) && (module.len() == m.len() || module[m.len()..].starts_with("::"));
+
+			if is_prefix && m.len() > matched {
+				matched = m.len();
+				threshold = *l;
+			}
+		}
+	}
+
+	level != Level::Off && level <= threshold
+}
+
+/// Writes a record as a whole under a lock, so those coming from different tasks do not interleave.
+///
+/// ISRs and fault handlers cannot wait for a task to release the lock, so they bypass it. Their output may get mixed
+/// into a record being written by the interrupted task, but it will get through.
+///
+/// Also taken by other writers sharing the log's output, e.g. the shell. `write` must not log itself, as the lock is
+/// not recursive
+///
+pub fn write_locked(write: impl FnOnce()) {
+	let isr = sync::in_isr();
+
+	if !isr {
+		unsafe {
+			<sync::Sem as Lock>::lock(&mut LOCK);
+		}
+	}
+
+	write();
+
+	if !isr {
+		unsafe {
+			<sync::Sem as Lock>::unlock(&mut LOCK);
+		}
+	}
+}
+
+/// Writes a record prefixed w/ the tick count, the current task's id., and the severity.
+///
+/// Not to be used directly, see `error!`, `warn!`, `info!`, `debug!`, and `trace!`
+///
+#[cfg(not(feature = "log-deferred"))]
+pub fn record(level: Level, module: &str, args: fmt::Arguments) {
+	if !enabled(level, module) {
+		return;
+	}
+
+	write_locked(|| {
+		let mut logger = Logger{};
+		let _ = match task::current() {
+			Some(id) => write!(logger, "[{} {}] {}: ", systick::ticks(), id, level.tag()),
+			None => write!(logger, "[{} -] {}: ", systick::ticks(), level.tag()),
+		};
+		let _ = logger.write_fmt(args);
+		let _ = logger.write_str("\r\n");
+	});
+}
+
+#[cfg(not(feature = "log-deferred"))]
+#[macro_export]
+macro_rules! log_record {
+	($level:expr, $($arg:tt)+) => {
+		if $level <= $crate::log::MAX_LEVEL {
+			$crate::log::record($level, module_path!(), format_args!($($arg)+));
+		}
+	};
+}
+
+#[macro_export]
+macro_rules! error {
+	($($arg:tt)+) => { $crate::log_record!($crate::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! warn {
+	($($arg:tt)+) => { $crate::log_record!($crate::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! info {
+	($($arg:tt)+) => { $crate::log_record!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! debug {
+	($($arg:tt)+) => { $crate::log_record!($crate::log::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! trace {
+	($($arg:tt)+) => { $crate::log_record!($crate::log::Level::Trace, $($arg)+) };
+}
+
+/// Kept for compatibility, logs at `Info` level
+///
+#[macro_export]
+macro_rules! log {
+	($($arg:tt)+) => { $crate::log_record!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[no_mangle]
+pub extern "C" fn log_arr(arr: *const usize, size: usize) {
+	unsafe {
+		for i in 0..size as isize {
+			log!("{} {}", i, *arr.offset(i));
+		}
+	}
+}
@@ -0,0 +1,108 @@
+use core::fmt;
+use core::ptr;
+
+const UP_BUFFER_SIZE: usize = 256;
+const CONTROL_BLOCK_ID: &[u8; 16] = b"SEGGER RTT\0\0\0\0\0\0";
+const MODE_NO_BLOCK_TRIM: u32 = 1;  // Write as much as fits into the buffer, discard the rest
+
+/// Ring buffer descriptor. The layout is dictated by SEGGER RTT, so a debug probe could find and read it
+///
+#[repr(C)]
+struct Buffer {
+	name: *const u8,
+	buffer: *mut u8,
+	size: u32,
+	wr_off: u32,  // Updated by the target
+	rd_off: u32,  // Updated by the host
+	flags: u32,
+}
+
+impl Buffer {
+	const fn new() -> Self {
+		Self {
+			name: ptr::null(),
+			buffer: ptr::null_mut(),
+			size: 0,
+			wr_off: 0,
+			rd_off: 0,
+			flags: 0,
+		}
+	}
+}
+
+/// RTT control block. The host locates it by scanning RAM for `CONTROL_BLOCK_ID`
+///
+#[repr(C)]
+struct ControlBlock {
+	id: [u8; 16],
+	max_up: i32,
+	max_down: i32,
+	up: Buffer,
+	down: Buffer,
+}
+
+#[no_mangle]
+static mut _SEGGER_RTT: ControlBlock = ControlBlock {
+	id: [0; 16],
+	max_up: 1,
+	max_down: 1,
+	up: Buffer::new(),
+	down: Buffer::new(),
+};
+
+static mut UP_BUFFER: [u8; UP_BUFFER_SIZE] = [0; UP_BUFFER_SIZE];
+
+/// Initializes the control block on the first use.
+///
+/// The id. is written last, so the host will not pick up a partially initialized control block
+///
+unsafe fn control_block() -> &'static mut ControlBlock {
+	if _SEGGER_RTT.id[0] == 0 {
+		_SEGGER_RTT.up.name = b"Terminal\0".as_ptr();
+		_SEGGER_RTT.up.buffer = UP_BUFFER.as_mut_ptr();
+		_SEGGER_RTT.up.size = UP_BUFFER_SIZE as u32;
+		_SEGGER_RTT.up.flags = MODE_NO_BLOCK_TRIM;
+		ptr::write_volatile(&mut _SEGGER_RTT.id, *CONTROL_BLOCK_ID);
+	}
+
+	&mut _SEGGER_RTT
+}
+
+/// Pushes bytes into the "up" (target to host) ring buffer. Returns the number of bytes written
+///
+pub fn write(buf: &[u8]) -> usize {
+	unsafe {
+		let up = &mut control_block().up;
+		let size = up.size as usize;
+		let rd_off = ptr::read_volatile(&up.rd_off) as usize;
+		let mut wr_off = ptr::read_volatile(&up.wr_off) as usize;
+		let mut written = 0;
+
+		for c in buf {
+			let next = (wr_off + 1) % size;
+
+			if next == rd_off {
+				break;  // The buffer is full, trim the rest
+			}
+
+			ptr::write_volatile(up.buffer.add(wr_off), *c);
+			wr_off = next;
+			written += 1;
+		}
+
+		ptr::write_volatile(&mut up.wr_off, wr_off as u32);  // Publish the data only after it has been copied
+
+		written
+	}
+}
+
+/// Logger writing into a RAM ring buffer, compatible with SEGGER RTT. Never blocks.
+///
+pub struct RttLogger;
+
+impl fmt::Write for RttLogger {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		write(s.as_bytes());
+		Ok(())
+	}
+}
@@ -0,0 +1,54 @@
+use core::fmt;
+use core::arch::asm;
+
+const SYS_OPEN: usize = 0x01;
+const SYS_WRITE: usize = 0x05;
+const OPEN_MODE_WRITE: usize = 4;  // "w", see ARM semihosting spec., SYS_OPEN
+const HANDLE_INVALID: usize = usize::MAX;
+
+static mut STDOUT: usize = HANDLE_INVALID;
+
+/// Issues a semihosting call.
+///
+/// Warning: when no debugger is attached, `bkpt` escalates into hard fault
+///
+unsafe fn call(operation: usize, arg: usize) -> usize {
+	let ret: usize;
+	asm!(
+		"bkpt 0xab",
+		inout("r0") operation => ret,
+		in("r1") arg,
+		options(nostack)
+	);
+	ret
+}
+
+/// Opens the host's console (special file ":tt") on the first use
+///
+unsafe fn stdout() -> usize {
+	if STDOUT == HANDLE_INVALID {
+		const TT: &[u8] = b":tt\0";
+		let params: [usize; 3] = [TT.as_ptr() as usize, OPEN_MODE_WRITE, TT.len() - 1];
+		STDOUT = call(SYS_OPEN, params.as_ptr() as usize);
+	}
+
+	STDOUT
+}
+
+pub fn write(buf: &[u8]) {
+	unsafe {
+		let params: [usize; 3] = [stdout(), buf.as_ptr() as usize, buf.len()];
+		call(SYS_WRITE, params.as_ptr() as usize);
+	}
+}
+
+/// Logger redirecting the output into the debugger's console through ARM semihosting.
+///
+pub struct SemihostingLogger;
+
+impl fmt::Write for SemihostingLogger {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		write(s.as_bytes());
+		Ok(())
+	}
+}
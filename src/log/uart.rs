@@ -0,0 +1,17 @@
+use crate::periph::usart;
+use core::fmt;
+
+pub fn write(buf: &[u8]) {
+	usart::write(buf);
+}
+
+/// Blocking logger over USART1. Requires `periph::usart` to be configured.
+///
+pub struct UartLogger;
+
+impl fmt::Write for UartLogger {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		write(s.as_bytes());
+		Ok(())
+	}
+}
@@ -14,10 +14,9 @@ mod reg;
 mod mem;
 mod tim;
 mod init;
+mod device;
 #[macro_use] mod log;
-
-use core::fmt::Write;
-use crate::log::Logger;
+#[cfg(feature = "shell")] mod shell;
 
 #[no_mangle]
 pub fn hard_fault(_sp: *const u32) -> ! {
@@ -25,40 +24,33 @@ pub fn hard_fault(_sp: *const u32) -> ! {
 	loop{}
 }
 
-#[no_mangle]
-pub fn tim14_irq() {
+fn tim14_irq() {
 	use reg::*;
 	unsafe {
-		wr!(TIM, "14", SR, UIF, 0);  // Clear interrupt flag, so it will not request interrupts indefinitely
-        wr!(SCB, ICSR, PENDSVSET, 1);  // Trigger PendSV interrupt for context switching
-	}
-}
-
-static mut COUNTER: u32 = 0;
-
-#[no_mangle]
-pub fn sys_tick() {
-	unsafe {
-		COUNTER += 1;
+		wr!(SCB, ICSR, PENDSVSET, 1);  // Trigger PendSV interrupt for context switching
 	}
 }
 
 fn task() {
 	loop {
-		periph::usart::write("I am a task".as_bytes());
+		log!("I am a task");
 	}
 }
 
 #[export_name = "main"]
 fn entry() -> ! {
+	let clocks = periph::rcc::Config::hsi().pll(12).freeze();  // HSI / 2 * 12 = 48 MHz
 	periph::rcc::configure();
 	periph::gpio::configure();
-	periph::usart::configure();
+	periph::usart::configure(&clocks);
 	periph::pendsv::configure();
+	periph::systick::configure(&clocks);
 
-	const TIM14_RESOLUTION_HZ: usize = 500;
-	periph::tim14::configure(TIM14_RESOLUTION_HZ);
-	periph::tim14::set_timeout(tim::Duration::Milliseconds(3000));
+	// TIM14 triggers context switching
+	const TIM14_RESOLUTION_HZ: usize = 1000;
+	let mut tim14 = periph::timer::Timer::<periph::timer::Tim14>::new(&clocks, TIM14_RESOLUTION_HZ);
+	tim14.set_callback(Some(tim14_irq));
+	tim14.start_periodic(tim::Duration::from_millis(3000));
 
 	let mut stack = thread::task::StaticAlloc::<512>::new();
 	log!("Allocated stack at {:?}", core::ptr::addr_of!(stack));
@@ -69,5 +61,15 @@ fn entry() -> ! {
 		Ok(_) => {},
 	};
 
+	#[cfg(feature = "shell")]
+	let mut shell_stack = thread::task::StaticAlloc::<768>::new();
+	#[cfg(feature = "shell")]
+	let mut shell_task = thread::task::Task::from_rs(shell::run, (&mut shell_stack).into());
+
+	#[cfg(feature = "shell")]
+	if shell_task.start().is_err() {
+		log!("Could not start the shell");
+	}
+
 	loop {}
 }
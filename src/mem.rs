@@ -5,6 +5,39 @@ extern "C" {
     fn free(ptr: *mut u8);
     fn calloc(num: usize, size: usize) -> *mut u8;
     fn realloc(ptr: *mut u8, size: usize) -> *mut u8;
+    fn mallinfo() -> Mallinfo;
+}
+
+/// newlib's `struct mallinfo`. Nano version of `malloc` only fills `arena`, `uordblks`, and `fordblks`
+///
+#[repr(C)]
+struct Mallinfo {
+    arena: usize,  // Total space allocated from the system (`_sbrk`)
+    ordblks: usize,
+    smblks: usize,
+    hblks: usize,
+    hblkhd: usize,
+    usmblks: usize,
+    fsmblks: usize,
+    uordblks: usize,  // Total allocated space
+    fordblks: usize,  // Total free space
+    keepcost: usize,
+}
+
+pub struct HeapUsage {
+    pub total: usize,  // Bytes claimed from the system
+    pub used: usize,
+    pub free: usize,
+}
+
+pub fn usage() -> HeapUsage {
+    let info = unsafe {mallinfo()};
+
+    HeapUsage {
+        total: info.arena,
+        used: info.uordblks,
+        free: info.fordblks,
+    }
 }
 
 pub struct Cmem();
@@ -0,0 +1,310 @@
+use crate::{regop, reg, tim, thread::sync, periph::{rcc, dma, gpio::{self, Pin, Analog}}};
+
+/// Analog inputs. Implemented for pins in `Analog` mode, provides the ADC channel number
+///
+pub trait AdcPin {
+	const CHANNEL: u8;
+}
+
+macro_rules! pin_impl {
+	($port:ident, $n:expr, $channel:expr) => {
+		impl AdcPin for Pin<gpio::$port, $n, Analog> {
+			const CHANNEL: u8 = $channel;
+		}
+	};
+}
+
+pin_impl!(A, 0, 0);
+pin_impl!(A, 1, 1);
+pin_impl!(A, 2, 2);
+pin_impl!(A, 3, 3);
+pin_impl!(A, 4, 4);
+pin_impl!(A, 5, 5);
+pin_impl!(A, 6, 6);
+pin_impl!(A, 7, 7);
+pin_impl!(B, 0, 8);
+pin_impl!(B, 1, 9);
+
+/// Internal channels
+///
+pub const TEMPERATURE: u8 = 16;
+pub const VREFINT: u8 = 17;
+
+/// Channel number of an analog pin, e.g. `adc.read(adc::channel(&pa0))`
+///
+pub fn channel<P: AdcPin>(_pin: &P) -> u8 {
+	P::CHANNEL
+}
+
+/// Sampling time in ADC clock cycles, SMPR values. Shared by all the channels
+///
+#[derive(Clone, Copy)]
+pub enum SampleTime {
+	Cycles1_5 = 0b000,
+	Cycles7_5 = 0b001,
+	Cycles13_5 = 0b010,
+	Cycles28_5 = 0b011,
+	Cycles41_5 = 0b100,
+	Cycles55_5 = 0b101,
+	Cycles71_5 = 0b110,
+	Cycles239_5 = 0b111,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+	Timeout,  // The ADC has not responded in time, e.g. its clock is off
+}
+
+// Factory calibration, measured at 3.3 V VDDA, see "Embedded temperature sensor", and "Embedded reference voltage"
+// in the datasheet
+const TS_CAL1: usize = 0x1fff_f7b8;  // Temperature sensor reading at 30 °C
+const VREFINT_CAL: usize = 0x1fff_f7ba;
+const CAL_VDDA_MV: u32 = 3300;
+const CAL_TEMPERATURE: i32 = 30;
+const AVG_SLOPE: i32 = 5336;  // 4.3 mV/°C in 1/1000ths of 12-bit LSB at 3.3 V
+const FULL_SCALE: u32 = 4095;
+const TS_START_US: u32 = 10;  // Temperature sensor, and VREFINT start-up time
+const ADC_MAX_HZ: usize = 14_000_000;
+const DMA_CHANNEL: usize = 1;
+const TIMEOUT: tim::Duration = tim::Duration::from_millis(10);  // The longest conversion at the slowest clock is ~2 ms
+
+const CKMODE_PCLK_DIV2: usize = 0b01;
+const CKMODE_PCLK_DIV4: usize = 0b10;
+
+unsafe fn read_cal(address: usize) -> u32 {
+	core::ptr::read_volatile(address as *const u16) as u32
+}
+
+fn wait_until(register: usize, mask: usize, set: bool) -> Result<(), Error> {
+	tim::wait_until(|| unsafe {regop::read_mask(register, mask) != 0} == set, TIMEOUT).map_err(|_| Error::Timeout)
+}
+
+/// 12-bit right aligned ADC. Channels of a sequence are converted in the ascending order of their numbers, a
+/// hardware limitation. Polled conversions use the wait mode, so a task being preempted does not lose data
+///
+pub struct Adc {
+	sample_time: SampleTime,
+}
+
+impl Adc {
+	/// Enables, and calibrates the ADC. It is clocked synchronously from PCLK, divided down to at most 14 MHz
+	///
+	pub fn new(clocks: &rcc::Clocks, sample_time: SampleTime) -> Result<Self, Error> {
+		let ckmode = if clocks.pclk / 2 <= ADC_MAX_HZ {CKMODE_PCLK_DIV2} else {CKMODE_PCLK_DIV4};
+		assert!(clocks.pclk / 4 <= ADC_MAX_HZ, "PCLK is too fast for the ADC");
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB2ENR_OFFSET, reg::RCC_APB2ENR_ADCEN_MSK);
+		}
+
+		let mut adc = Adc {sample_time};
+
+		unsafe {
+			regop::write_mask(ckmode, reg::ADC1_BASE + reg::ADC_CFGR2_OFFSET, reg::ADC_CFGR2_CKMODE_MSK);
+		}
+
+		adc.calibrate()?;
+		adc.set_sample_time(sample_time)?;
+
+		Ok(adc)
+	}
+
+	/// Runs the self-calibration, returns the calibration factor. The ADC is disabled for its duration
+	///
+	pub fn calibrate(&mut self) -> Result<u8, Error> {
+		self.disable()?;
+
+		unsafe {
+			regop::write_mask(0, reg::ADC1_BASE + reg::ADC_CFGR1_OFFSET, reg::ADC_CFGR1_DMAEN_MSK);
+			regop::write_mask(1, reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADCAL_MSK);
+		}
+
+		wait_until(reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADCAL_MSK, false)?;
+		let factor = unsafe {regop::read(reg::ADC1_BASE + reg::ADC_DR_OFFSET) as u8};
+		self.enable()?;
+
+		Ok(factor)
+	}
+
+	fn enable(&mut self) -> Result<(), Error> {
+		unsafe {
+			regop::write(reg::ADC_ISR_ADRDY_MSK, reg::ADC1_BASE + reg::ADC_ISR_OFFSET);
+			regop::write_mask(1, reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADEN_MSK);
+		}
+
+		wait_until(reg::ADC1_BASE + reg::ADC_ISR_OFFSET, reg::ADC_ISR_ADRDY_MSK, true)
+	}
+
+	fn disable(&mut self) -> Result<(), Error> {
+		self.stop()?;
+
+		unsafe {
+			if regop::read_mask(reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADEN_MSK) == 0 {
+				return Ok(());
+			}
+
+			regop::write_mask(1, reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADDIS_MSK);
+		}
+
+		wait_until(reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADEN_MSK, false)
+	}
+
+	/// Stops an ongoing conversion first, as SMPR may only be written while the ADC is idle
+	///
+	pub fn set_sample_time(&mut self, sample_time: SampleTime) -> Result<(), Error> {
+		self.stop()?;
+		self.sample_time = sample_time;
+
+		unsafe {
+			regop::write_mask(sample_time as usize, reg::ADC1_BASE + reg::ADC_SMPR_OFFSET, reg::ADC_SMPR_SMP_MSK);
+		}
+
+		Ok(())
+	}
+
+	/// Stops an ongoing conversion, e.g. a continuous one. The configuration may only be changed while stopped
+	///
+	pub fn stop(&mut self) -> Result<(), Error> {
+		let ret = unsafe {
+			if regop::read_mask(reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADSTART_MSK) != 0 {
+				regop::write_mask(1, reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADSTP_MSK);
+				wait_until(reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADSTP_MSK, false)
+			} else {
+				Ok(())
+			}
+		};
+
+		dma::stop(DMA_CHANNEL);
+
+		ret
+	}
+
+	/// Selects the channels, sets the mode, and starts converting
+	///
+	fn start(&mut self, channels: u32, cfgr1: usize) -> Result<(), Error> {
+		assert!(channels != 0 && channels >> (VREFINT + 1) == 0, "Invalid ADC channel selection");
+		self.stop()?;
+
+		unsafe {
+			let cfgr1_fields = reg::ADC_CFGR1_CONT_MSK | reg::ADC_CFGR1_WAIT_MSK | reg::ADC_CFGR1_DMAEN_MSK
+				| reg::ADC_CFGR1_DMACFG_MSK;
+			let val = regop::read(reg::ADC1_BASE + reg::ADC_CFGR1_OFFSET) & !cfgr1_fields;
+			regop::write(val | cfgr1, reg::ADC1_BASE + reg::ADC_CFGR1_OFFSET);
+			regop::write(channels as usize, reg::ADC1_BASE + reg::ADC_CHSELR_OFFSET);
+			regop::write(reg::ADC_ISR_EOC_MSK | reg::ADC_ISR_EOS_MSK | reg::ADC_ISR_OVR_MSK,
+				reg::ADC1_BASE + reg::ADC_ISR_OFFSET);
+			regop::write_mask(1, reg::ADC1_BASE + reg::ADC_CR_OFFSET, reg::ADC_CR_ADSTART_MSK);
+		}
+
+		Ok(())
+	}
+
+	fn wait_eoc(&self) -> Result<u16, Error> {
+		wait_until(reg::ADC1_BASE + reg::ADC_ISR_OFFSET, reg::ADC_ISR_EOC_MSK, true)?;
+
+		Ok(unsafe {regop::read(reg::ADC1_BASE + reg::ADC_DR_OFFSET) as u16})  // Clears EOC
+	}
+
+	/// Single conversion of a channel
+	///
+	pub fn read(&mut self, channel: u8) -> Result<u16, Error> {
+		self.start(1 << channel, reg::ADC_CFGR1_WAIT_MSK)?;
+		self.wait_eoc()
+	}
+
+	/// Converts a sequence, a bit per channel in `channels`, into `values`, one per selected channel
+	///
+	pub fn scan(&mut self, channels: u32, values: &mut [u16]) -> Result<(), Error> {
+		assert!(values.len() == channels.count_ones() as usize, "ADC scan buffer does not match the sequence");
+		self.start(channels, reg::ADC_CFGR1_WAIT_MSK)?;
+
+		for value in values.iter_mut() {
+			*value = self.wait_eoc()?;
+		}
+
+		Ok(())
+	}
+
+	/// Converts a channel continuously, the latest result is returned by `last`. Stopped by `stop`
+	///
+	pub fn start_continuous(&mut self, channel: u8) -> Result<(), Error> {
+		// The data register is overwritten, rather than the conversions stalling
+		unsafe {
+			regop::write_mask(1, reg::ADC1_BASE + reg::ADC_CFGR1_OFFSET, reg::ADC_CFGR1_OVRMOD_MSK);
+		}
+
+		self.start(1 << channel, reg::ADC_CFGR1_CONT_MSK)
+	}
+
+	pub fn last(&self) -> u16 {
+		unsafe {
+			regop::read(reg::ADC1_BASE + reg::ADC_DR_OFFSET) as u16
+		}
+	}
+
+	/// Converts the sequence continuously into `buffer`, through DMA channel 1 in circular mode. The buffer holds
+	/// one, or more whole sequences. `callback` is invoked from the ISR when either half of the buffer is filled
+	///
+	pub fn start_circular_dma(&mut self, channels: u32, buffer: &'static mut [u16],
+		callback: Option<dma::Callback>) -> Result<(), Error> {
+		let sequence = channels.count_ones() as usize;
+		assert!(sequence != 0 && buffer.len() % sequence == 0, "ADC DMA buffer must hold whole sequences");
+		self.stop()?;
+		dma::set_callback(DMA_CHANNEL, callback);
+		dma::start(DMA_CHANNEL, &dma::Transfer {peripheral: reg::ADC1_BASE + reg::ADC_DR_OFFSET,
+			memory: buffer.as_mut_ptr() as usize, count: buffer.len(), size: dma::Size::HalfWord,
+			direction: dma::Direction::PeripheralToMemory, memory_increment: true, circular: true});
+		self.start(channels, reg::ADC_CFGR1_CONT_MSK | reg::ADC_CFGR1_DMAEN_MSK | reg::ADC_CFGR1_DMACFG_MSK)
+	}
+
+	/// Reads an internal channel. They need a long sampling time, so the maximum one is used for the duration. An
+	/// ongoing conversion is stopped, as CCR, and SMPR may only be changed while the ADC is idle
+	///
+	fn read_internal(&mut self, channel: u8, enable_msk: usize) -> Result<u32, Error> {
+		let sample_time = self.sample_time;
+		self.stop()?;
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(1, reg::ADC_BASE + reg::ADC_COMMON_CCR_OFFSET, enable_msk);
+		}
+
+		tim::delay_us(TS_START_US);
+		self.set_sample_time(SampleTime::Cycles239_5)?;
+		let value = self.read(channel).map(|v| v as u32);
+		let restored = self.stop().and_then(|_| self.set_sample_time(sample_time));
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(0, reg::ADC_BASE + reg::ADC_COMMON_CCR_OFFSET, enable_msk);
+		}
+
+		restored.and(value)
+	}
+
+	/// Measures the analog supply voltage against VREFINT, mV
+	///
+	pub fn vdda_mv(&mut self) -> Result<u32, Error> {
+		let vrefint = self.read_internal(VREFINT, reg::ADC_CCR_VREFEN_MSK)?;
+
+		Ok(CAL_VDDA_MV * unsafe {read_cal(VREFINT_CAL)} / core::cmp::max(vrefint, 1))
+	}
+
+	/// Converts a raw reading into mV, given the supply voltage, e.g. of `vdda_mv`
+	///
+	pub fn to_mv(&self, value: u16, vdda_mv: u32) -> u32 {
+		value as u32 * vdda_mv / FULL_SCALE
+	}
+
+	/// Die temperature, °C. STM32F030 has a single calibration point, so the slope is the datasheet's typical one
+	///
+	pub fn temperature(&mut self) -> Result<i32, Error> {
+		let vdda = self.vdda_mv()?;
+		let raw = self.read_internal(TEMPERATURE, reg::ADC_CCR_TSEN_MSK)?;
+		let data = (raw * vdda / CAL_VDDA_MV) as i32;  // As if measured at the calibration VDDA
+		let cal = unsafe {read_cal(TS_CAL1)} as i32;
+
+		Ok((cal - data) * 1000 / AVG_SLOPE + CAL_TEMPERATURE)
+	}
+}
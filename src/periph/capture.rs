@@ -0,0 +1,268 @@
+use crate::{regop, reg, thread::sync, periph::{nvic, irq, timer::{self, Timer, Instance, ChannelPin}}};
+use core::marker::PhantomData;
+
+/// What a channel captures
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+	Rising,
+	Falling,
+	/// Alternates between rising and falling edges, so both the period, and the high pulse width are measured
+	Pulse,
+}
+
+/// Called from the ISR w/ the 32-bit timestamp of a captured edge
+///
+pub type Callback = fn(timestamp: u32);
+
+#[derive(Clone, Copy)]
+struct ChannelState {
+	mode: Mode,
+	callback: Option<Callback>,
+	started: bool,  // At least one edge has been captured
+	last: u32,  // Timestamp of the last captured edge
+	rising: u32,  // Timestamp of the last rising edge, `Mode::Pulse`
+	period: u32,  // Ticks between 2 edges of the same kind, 0 - not measured yet
+	width: u32,  // Ticks between a rising, and the following falling edge, 0 - not measured yet
+}
+
+const CHANNEL_STATE: ChannelState = ChannelState {mode: Mode::Rising, callback: None, started: false, last: 0,
+	rising: 0, period: 0, width: 0};
+
+// TIM1, TIM3 have 4 channels, the rest have 1. Per-instance offsets into `CHANNELS`, indexed by `Instance::INDEX`
+const CHANNEL_OFFSETS: [usize; timer::NINSTANCES] = [0, 4, 8, 9, 10];
+const NCHANNELS: usize = 11;
+
+static mut CHANNELS: [ChannelState; NCHANNELS] = [CHANNEL_STATE; NCHANNELS];
+static mut OVERFLOWS: [u16; timer::NINSTANCES] = [0; timer::NINSTANCES];  // Upper halves of the 32-bit timestamps
+
+/// Input capture unit. The timer's 16-bit counter runs freely, and is extended to 32 bits by counting its overflows
+/// in the ISR, so timestamps wrap around after `2^32 / tick_hz` seconds
+///
+pub struct Capture<T: Instance> {
+	timer: Timer<T>,
+}
+
+impl<T: Instance> Capture<T> {
+	pub fn new(mut timer: Timer<T>) -> Self {
+		unsafe {
+			OVERFLOWS[T::INDEX] = 0;
+		}
+
+		irq::register(T::IRQ, &isr::<T>);
+		irq::register(T::CC_IRQ, &isr::<T>);
+		timer.start_free_running();
+
+		unsafe {
+			regop::write_mask(1, T::BASE + reg::TIM_DIER_OFFSET, reg::TIM_DIER_UIE_MSK);
+		}
+
+		nvic::set_priority(T::IRQ, nvic::PRIORITY_KERNEL);
+		nvic::set_priority(T::CC_IRQ, nvic::PRIORITY_KERNEL);
+		nvic::enable(T::IRQ);
+		nvic::enable(T::CC_IRQ);
+
+		Capture {timer}
+	}
+
+	pub fn tick_hz(&self) -> usize {
+		self.timer.tick_hz()
+	}
+
+	/// Current 32-bit timestamp, comparable w/ the captured ones
+	///
+	pub fn now(&self) -> u32 {
+		let _critical = sync::Critical::new();
+
+		unsafe {
+			let counter = regop::read(T::BASE + reg::TIM_CNT_OFFSET) as u32 & 0xffff;
+			let overflow = regop::read_mask(T::BASE + reg::TIM_SR_OFFSET, reg::TIM_SR_UIF_MSK) != 0;
+			extend(OVERFLOWS[T::INDEX], counter, overflow)
+		}
+	}
+
+	/// Routes the pin to channel #C, and starts capturing
+	///
+	pub fn channel<const C: u8, P: ChannelPin<T, C>>(&mut self, _pin: P, mode: Mode) -> InputChannel<T, C> {
+		let _ = InputChannel::<T, C>::VALID;
+		let channel = InputChannel {tick_hz: self.timer.tick_hz(), _t: PhantomData};
+		const CC_INPUT_TI: usize = 0b01;  // ICx is mapped on TIx, i.e. the channel's own pin
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			CHANNELS[InputChannel::<T, C>::slot()] = ChannelState {mode, ..CHANNEL_STATE};
+
+			let shift = ((C as usize - 1) % 2) * 8;
+			let ccmr = T::BASE + if C <= 2 {reg::TIM_CCMR1_OFFSET} else {reg::TIM_CCMR2_OFFSET};
+			let ccer = T::BASE + reg::TIM_CCER_OFFSET;
+			let ccer_shift = (C as usize - 1) * 4;
+			regop::write_mask(0, ccer, reg::TIM_CCER_CC1E_MSK << ccer_shift);  // CCxS is writable only when disabled
+			regop::write_mask(CC_INPUT_TI, ccmr, reg::TIM_CCMR1_CC1S_MSK << shift);
+			regop::write_mask(0, ccmr, reg::TIM_CCMR1_IC1PSC_MSK << shift);  // Capture every edge
+			regop::write_mask((mode == Mode::Falling) as usize, ccer, reg::TIM_CCER_CC1P_MSK << ccer_shift);
+			regop::write_mask(0, ccer, reg::TIM_CCER_CC1NP_MSK << ccer_shift);
+			regop::write_mask(1, ccer, reg::TIM_CCER_CC1E_MSK << ccer_shift);
+			regop::write_mask(1, T::BASE + reg::TIM_DIER_OFFSET, reg::TIM_DIER_CC1IE_MSK << (C as usize - 1));
+		}
+
+		channel
+	}
+}
+
+/// Combines the overflow count w/ a 16-bit counter value. If an overflow is pending, but has not been counted yet,
+/// small values have been sampled after it
+///
+fn extend(overflows: u16, counter: u32, overflow_pending: bool) -> u32 {
+	let overflows = if overflow_pending && counter < 0x8000 {overflows.wrapping_add(1)} else {overflows};
+	((overflows as u32) << 16) | counter
+}
+
+/// A capture channel #C of timer T
+///
+pub struct InputChannel<T: Instance, const C: u8> {
+	tick_hz: usize,
+	_t: PhantomData<T>,
+}
+
+impl<T: Instance, const C: u8> InputChannel<T, C> {
+	const VALID: () = assert!(C >= 1 && C <= 4, "Timers have channels 1..4");
+
+	fn slot() -> usize {
+		CHANNEL_OFFSETS[T::INDEX] + C as usize - 1
+	}
+
+	fn state(&self) -> ChannelState {
+		let _critical = sync::Critical::new();
+
+		unsafe {CHANNELS[Self::slot()]}
+	}
+
+	/// Digital filter, [0; 15]: the number of consecutive equal samples required to validate a transition, and the
+	/// sampling rate. See ICxF in the reference manual. 0 - no filtering
+	///
+	pub fn set_filter(&self, filter: u8) {
+		let shift = ((C as usize - 1) % 2) * 8;
+		let ccmr = T::BASE + if C <= 2 {reg::TIM_CCMR1_OFFSET} else {reg::TIM_CCMR2_OFFSET};
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(filter as usize, ccmr, reg::TIM_CCMR1_IC1F_MSK << shift);
+		}
+	}
+
+	/// Sets a function to be called from the ISR on each captured edge
+	///
+	pub fn set_callback(&self, callback: Option<Callback>) {
+		let _critical = sync::Critical::new();
+
+		unsafe {
+			CHANNELS[Self::slot()].callback = callback;
+		}
+	}
+
+	/// Timestamp of the last captured edge. Compare it w/ `Capture::now` to detect a stopped signal
+	///
+	pub fn last_capture(&self) -> u32 {
+		self.state().last
+	}
+
+	/// Ticks between the 2 last edges of the same kind, `None` until 2 edges have been captured
+	///
+	pub fn period_ticks(&self) -> Option<u32> {
+		Some(self.state().period).filter(|p| *p != 0)
+	}
+
+	/// High pulse width in ticks, only measured in `Mode::Pulse`
+	///
+	pub fn pulse_width_ticks(&self) -> Option<u32> {
+		Some(self.state().width).filter(|w| *w != 0)
+	}
+
+	/// Input signal frequency, mHz for resolution on slow signals, like those of tachometers
+	///
+	pub fn frequency_mhz(&self) -> Option<usize> {
+		self.period_ticks().map(|p| (self.tick_hz as u64 * 1000 / p as u64) as usize)
+	}
+
+	pub fn frequency_hz(&self) -> Option<usize> {
+		self.period_ticks().map(|p| self.tick_hz / p as usize)
+	}
+
+	/// High pulse width in microseconds, e.g. ultrasonic rangefinder's echo
+	///
+	pub fn pulse_width_us(&self) -> Option<usize> {
+		self.pulse_width_ticks().map(|w| (w as u64 * 1_000_000 / self.tick_hz as u64) as usize)
+	}
+
+	pub fn disable(&self) {
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(0, T::BASE + reg::TIM_DIER_OFFSET, reg::TIM_DIER_CC1IE_MSK << (C as usize - 1));
+			regop::write_mask(0, T::BASE + reg::TIM_CCER_OFFSET, reg::TIM_CCER_CC1E_MSK << ((C as usize - 1) * 4));
+		}
+	}
+}
+
+/// Updates the channel's measurements
+///
+unsafe fn on_capture<T: Instance>(channel: usize, timestamp: u32) {
+	let state = &mut CHANNELS[CHANNEL_OFFSETS[T::INDEX] + channel];
+
+	match state.mode {
+		Mode::Rising | Mode::Falling => {
+			state.period = timestamp.wrapping_sub(state.last);
+		},
+		Mode::Pulse => {
+			// Edge polarity is flipped after each capture, CCxP tells which one has been captured
+			let ccer = T::BASE + reg::TIM_CCER_OFFSET;
+			let ccxp = reg::TIM_CCER_CC1P_MSK << (channel * 4);
+			let was_falling = regop::read_mask(ccer, ccxp) != 0;
+
+			if was_falling {
+				state.width = timestamp.wrapping_sub(state.rising);
+			} else {
+				state.period = timestamp.wrapping_sub(state.rising);
+				state.rising = timestamp;
+			}
+
+			regop::write_mask(!was_falling as usize, ccer, ccxp);
+		},
+	}
+
+	// The first edge has nothing to be measured against
+	if !state.started {
+		state.period = 0;
+		state.width = 0;
+	}
+
+	state.started = true;
+	state.last = timestamp;
+
+	if let Some(callback) = state.callback {
+		callback(timestamp);
+	}
+}
+
+fn isr<T: Instance>() {
+	unsafe {
+		let sr = regop::read(T::BASE + reg::TIM_SR_OFFSET);
+		let dier = regop::read(T::BASE + reg::TIM_DIER_OFFSET);
+		let overflow = sr & reg::TIM_SR_UIF_MSK != 0;
+		let overflows = OVERFLOWS[T::INDEX];
+
+		for channel in 0..4 {
+			if sr & dier & (reg::TIM_SR_CC1IF_MSK << channel) == 0 {
+				continue;
+			}
+
+			let counter = regop::read(T::BASE + reg::TIM_CCR1_OFFSET + channel * 4) as u32 & 0xffff;  // Clears CCxIF
+			regop::write(!(reg::TIM_SR_CC1OF_MSK << channel), T::BASE + reg::TIM_SR_OFFSET);  // Over-capture, an edge has been missed
+			on_capture::<T>(channel, extend(overflows, counter, overflow));
+		}
+
+		if overflow {
+			regop::write(!reg::TIM_SR_UIF_MSK, T::BASE + reg::TIM_SR_OFFSET);
+			OVERFLOWS[T::INDEX] = overflows.wrapping_add(1);
+		}
+	}
+}
@@ -0,0 +1,87 @@
+use crate::{regop, reg, thread::sync, thread::sync::Lock};
+use core::ptr;
+
+/// The same CRC-32 in software. It depends on `core` only, so it is shared w/ the host crate "tools/crc32", which
+/// unit-tests it, e.g. to check protocol code on the host
+///
+pub mod soft;
+
+const FINAL_XOR: u32 = 0xffff_ffff;
+const REV_IN_BYTE: usize = 0b01;
+const REV_IN_WORD: usize = 0b11;
+const DR: usize = reg::CRC_BASE + reg::CRC_DR_OFFSET;
+
+static mut UNIT: sync::Sem = sync::Sem::new(1, 1);
+
+/// CRC-32 as of Ethernet, zlib, and PNG: polynomial 0x04C11DB7, reflected, initial value, and final XOR 0xFFFFFFFF.
+/// The unit's polynomial, and initial value are fixed to these on STM32F030, the reflection is done by REV_IN, and
+/// REV_OUT. Words are fed as their little-endian bytes, so `update_words(&[w])` equals `update(&w.to_le_bytes())`.
+/// Holds the unit exclusively, blocking other tasks' `new` until dropped
+///
+pub struct Crc {
+	_private: (),
+}
+
+impl Crc {
+	pub fn new() -> Crc {
+		unsafe {
+			<sync::Sem as Lock>::lock(&mut UNIT);
+
+			{
+				let _critical = sync::Critical::new();
+				regop::write_mask(1, reg::RCC_BASE + reg::RCC_AHBENR_OFFSET, reg::RCC_AHBENR_CRCEN_MSK);
+			}
+
+			regop::write_mask(1, reg::CRC_BASE + reg::CRC_CR_OFFSET, reg::CRC_CR_REV_OUT_MSK);
+			regop::write_mask(1, reg::CRC_BASE + reg::CRC_CR_OFFSET, reg::CRC_CR_RESET_MSK);
+		}
+
+		Crc {_private: ()}
+	}
+
+	pub fn update(&mut self, data: &[u8]) -> &mut Self {
+		unsafe {
+			regop::write_mask(REV_IN_BYTE, reg::CRC_BASE + reg::CRC_CR_OFFSET, reg::CRC_CR_REV_IN_MSK);
+
+			for b in data {
+				ptr::write_volatile(DR as *mut u8, *b);
+			}
+		}
+
+		self
+	}
+
+	pub fn update_words(&mut self, data: &[u32]) -> &mut Self {
+		unsafe {
+			regop::write_mask(REV_IN_WORD, reg::CRC_BASE + reg::CRC_CR_OFFSET, reg::CRC_CR_REV_IN_MSK);
+
+			for w in data {
+				ptr::write_volatile(DR as *mut u32, *w);
+			}
+		}
+
+		self
+	}
+
+	/// Checksum of the data fed so far. Feeding may go on
+	///
+	pub fn value(&self) -> u32 {
+		unsafe {ptr::read_volatile(DR as *const u32) ^ FINAL_XOR}
+	}
+}
+
+impl Drop for Crc {
+	fn drop(&mut self) {
+		unsafe {
+			<sync::Sem as Lock>::unlock(&mut UNIT);
+		}
+	}
+}
+
+pub fn checksum(data: &[u8]) -> u32 {
+	Crc::new().update(data).value()
+}
+
+pub fn checksum_words(data: &[u32]) -> u32 {
+	Crc::new().update_words(data).value()
+}
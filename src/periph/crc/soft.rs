@@ -0,0 +1,71 @@
+const POLY_REFLECTED: u32 = 0xedb8_8320;
+const INIT: u32 = 0xffff_ffff;
+const FINAL_XOR: u32 = 0xffff_ffff;
+const TABLE: [u32; 16] = table();  // Nibble-wise, 64 bytes of flash
+
+const fn table() -> [u32; 16] {
+	let mut table = [0; 16];
+	let mut i = 0;
+
+	while i < 16 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+
+		while bit < 4 {
+			crc = if crc & 1 != 0 {(crc >> 1) ^ POLY_REFLECTED} else {crc >> 1};
+			bit += 1;
+		}
+
+		table[i] = crc;
+		i += 1;
+	}
+
+	table
+}
+
+#[derive(Clone, Copy)]
+pub struct Crc {
+	crc: u32,
+}
+
+impl Crc {
+	pub const fn new() -> Crc {
+		Crc {crc: INIT}
+	}
+
+	pub fn update(&mut self, data: &[u8]) -> &mut Self {
+		for b in data {
+			self.crc ^= *b as u32;
+			self.crc = (self.crc >> 4) ^ TABLE[(self.crc & 0xf) as usize];
+			self.crc = (self.crc >> 4) ^ TABLE[(self.crc & 0xf) as usize];
+		}
+
+		self
+	}
+
+	pub fn update_words(&mut self, data: &[u32]) -> &mut Self {
+		for w in data {
+			self.update(&w.to_le_bytes());
+		}
+
+		self
+	}
+
+	pub fn value(&self) -> u32 {
+		self.crc ^ FINAL_XOR
+	}
+}
+
+impl Default for Crc {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+pub fn checksum(data: &[u8]) -> u32 {
+	Crc::new().update(data).value()
+}
+
+pub fn checksum_words(data: &[u32]) -> u32 {
+	Crc::new().update_words(data).value()
+}
@@ -0,0 +1,186 @@
+use crate::{regop, reg, thread::sync, thread::sync::{Lock, LockIsr}, periph::{nvic, irq}};
+
+/// DMA1 channels. The request mapping is fixed on STM32F030, e.g. ADC - 1, SPI1 RX - 2, SPI1 TX - 3, see "DMA request
+/// mapping" in the reference manual
+///
+pub const NCHANNELS: usize = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+	PeripheralToMemory,
+	MemoryToPeripheral,
+}
+
+/// Transfer item size, both peripheral and memory sides use the same one
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Size {
+	Byte = 0b00,
+	HalfWord = 0b01,
+	Word = 0b10,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+	HalfTransfer,
+	TransferComplete,
+	TransferError,
+}
+
+/// Called from the ISR. Intended for circular transfers, where a task cannot wait for completion
+///
+pub type Callback = fn(channel: usize, event: Event);
+
+#[derive(Debug)]
+pub enum Error {
+	Transfer,  // Bus error, e.g. an address outside of RAM / peripherals
+}
+
+/// Transfer description. `peripheral`, and `memory` are addresses
+///
+pub struct Transfer {
+	pub peripheral: usize,
+	pub memory: usize,
+	pub count: usize,  // Number of items, up to 65535
+	pub size: Size,
+	pub direction: Direction,
+	pub memory_increment: bool,
+	pub circular: bool,
+}
+
+const SIGNAL: sync::Sem = sync::Sem::new(0, 1);
+
+static mut SIGNALS: [sync::Sem; NCHANNELS] = [SIGNAL; NCHANNELS];
+static mut ERRORS: [bool; NCHANNELS] = [false; NCHANNELS];
+static mut CALLBACKS: [Option<Callback>; NCHANNELS] = [None; NCHANNELS];
+
+/// Channels are numbered from 1, as in the reference manual
+///
+fn base(channel: usize) -> usize {
+	assert!(channel >= 1 && channel <= NCHANNELS);
+	reg::DMA1_CHANNEL1_BASE + (channel - 1) * (reg::DMA1_CHANNEL2_BASE - reg::DMA1_CHANNEL1_BASE)
+}
+
+fn interrupt(channel: usize) -> nvic::Interrupt {
+	match channel {
+		1 => nvic::Interrupt::Dma1Ch1,
+		2 | 3 => nvic::Interrupt::Dma1Ch2_3,
+		_ => nvic::Interrupt::Dma1Ch4_5,
+	}
+}
+
+/// ISR / IFCR flags of a channel: global, transfer complete, half transfer, transfer error
+///
+fn flags(channel: usize, mask: usize) -> usize {
+	mask << ((channel - 1) * 4)
+}
+
+/// Configures, and enables the channel. The caller must keep the memory valid until the transfer is complete or
+/// stopped, see `wait` and `stop`
+///
+pub fn start(channel: usize, transfer: &Transfer) {
+	let base = base(channel);
+	let irq = interrupt(channel);
+
+	unsafe {
+		{
+			let _critical = sync::Critical::new();
+			regop::write_mask(1, reg::RCC_BASE + reg::RCC_AHBENR_OFFSET, reg::RCC_AHBENR_DMAEN_MSK);
+		}
+
+		regop::write(0, base + reg::DMA_CHANNEL_CCR_OFFSET);  // The channel must be disabled to be configured
+		regop::write(flags(channel, 0b1111), reg::DMA1_BASE + reg::DMA_IFCR_OFFSET);
+		ERRORS[channel - 1] = false;
+		<sync::Sem as LockIsr>::try_lock(&mut SIGNALS[channel - 1]);  // Drop a stale completion
+
+		regop::write(transfer.peripheral, base + reg::DMA_CHANNEL_CPAR_OFFSET);
+		regop::write(transfer.memory, base + reg::DMA_CHANNEL_CMAR_OFFSET);
+		regop::write(transfer.count, base + reg::DMA_CHANNEL_CNDTR_OFFSET);
+
+		let size = transfer.size as usize;
+		let ccr = reg::DMA_CCR_TCIE_MSK
+			| reg::DMA_CCR_TEIE_MSK
+			| if transfer.circular {reg::DMA_CCR_CIRC_MSK | reg::DMA_CCR_HTIE_MSK} else {0}
+			| if transfer.direction == Direction::MemoryToPeripheral {reg::DMA_CCR_DIR_MSK} else {0}
+			| if transfer.memory_increment {reg::DMA_CCR_MINC_MSK} else {0}
+			| (size << reg::DMA_CCR_PSIZE_POS)
+			| (size << reg::DMA_CCR_MSIZE_POS);
+		regop::write(ccr, base + reg::DMA_CHANNEL_CCR_OFFSET);
+	}
+
+	irq::register(irq, &isr);
+	nvic::set_priority(irq, nvic::PRIORITY_KERNEL);
+	nvic::enable(irq);
+
+	unsafe {
+		regop::write_mask(1, base + reg::DMA_CHANNEL_CCR_OFFSET, reg::DMA_CCR_EN_MSK);
+	}
+}
+
+pub fn stop(channel: usize) {
+	unsafe {
+		regop::write_mask(0, base(channel) + reg::DMA_CHANNEL_CCR_OFFSET, reg::DMA_CCR_EN_MSK);
+	}
+}
+
+/// Number of items left to transfer
+///
+pub fn remaining(channel: usize) -> usize {
+	unsafe {
+		regop::read(base(channel) + reg::DMA_CHANNEL_CNDTR_OFFSET) & 0xffff
+	}
+}
+
+/// Blocks the calling task until the (non-circular) transfer is complete
+///
+pub fn wait(channel: usize) -> Result<(), Error> {
+	unsafe {
+		<sync::Sem as Lock>::lock(&mut SIGNALS[channel - 1]);
+
+		if ERRORS[channel - 1] {Err(Error::Transfer)} else {Ok(())}
+	}
+}
+
+pub fn set_callback(channel: usize, callback: Option<Callback>) {
+	let _critical = sync::Critical::new();
+
+	unsafe {
+		CALLBACKS[channel - 1] = callback;
+	}
+}
+
+fn isr() {
+	unsafe {
+		let isr = regop::read(reg::DMA1_BASE + reg::DMA_ISR_OFFSET);
+
+		for channel in 1..=NCHANNELS {
+			let pending = isr & flags(channel, reg::DMA_ISR_TCIF1_MSK | reg::DMA_ISR_HTIF1_MSK | reg::DMA_ISR_TEIF1_MSK);
+
+			if pending == 0 {
+				continue;
+			}
+
+			regop::write(pending, reg::DMA1_BASE + reg::DMA_IFCR_OFFSET);  // IFCR bits mirror those of ISR
+			let base = base(channel);
+			let circular = regop::read_mask(base + reg::DMA_CHANNEL_CCR_OFFSET, reg::DMA_CCR_CIRC_MSK) != 0;
+			let event = if pending & flags(channel, reg::DMA_ISR_TEIF1_MSK) != 0 {
+				// The hardware disables the channel on error
+				ERRORS[channel - 1] = true;
+				Event::TransferError
+			} else if pending & flags(channel, reg::DMA_ISR_TCIF1_MSK) != 0 {
+				Event::TransferComplete
+			} else {
+				Event::HalfTransfer
+			};
+
+			if event != Event::HalfTransfer && !circular {
+				regop::write_mask(0, base + reg::DMA_CHANNEL_CCR_OFFSET, reg::DMA_CCR_EN_MSK);
+				<sync::Sem as LockIsr>::unlock(&mut SIGNALS[channel - 1]);
+			}
+
+			if let Some(callback) = CALLBACKS[channel - 1] {
+				callback(channel, event);
+			}
+		}
+	}
+}
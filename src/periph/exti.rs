@@ -0,0 +1,118 @@
+use crate::{regop, reg, thread::sync, thread::sync::{Lock, LockIsr}};
+use crate::periph::{nvic, irq, gpio::{Pin, Port, Input, Pull}};
+
+const NLINES: usize = 16;  // GPIO lines. Lines 16+ are wired to internal peripherals
+
+/// Line trigger
+///
+pub enum Edge {
+	Rising,
+	Falling,
+	Both,
+}
+
+pub type Callback = fn(line: u8);
+
+const SIGNAL: sync::Sem = sync::Sem::new(0, 1);
+
+static mut CALLBACKS: [Option<Callback>; NLINES] = [None; NLINES];
+static mut SIGNALS: [sync::Sem; NLINES] = [SIGNAL; NLINES];
+
+/// Routes an input pin to EXTI line #N, and enables interrupts on the line. Returns the line number.
+///
+/// A line can only be connected to one port at a time, so e.g. PA0 and PB0 cannot be used simultaneously
+///
+pub fn configure<P: Port, const N: u8, U: Pull>(_pin: &Pin<P, N, Input<U>>, edge: Edge) -> u8 {
+	let line = N as usize;
+	let exticr = reg::SYSCFG_BASE + reg::SYSCFG_EXTICR_0_OFFSET + (line / 4) * 4;
+	let (rising, falling) = match edge {
+		Edge::Rising => (1, 0),
+		Edge::Falling => (0, 1),
+		Edge::Both => (1, 1),
+	};
+	let (interrupt, handler): (_, irq::Handler) = match line {
+		0..=1 => (nvic::Interrupt::Exti0_1, &exti0_1_irq),
+		2..=3 => (nvic::Interrupt::Exti2_3, &exti2_3_irq),
+		_ => (nvic::Interrupt::Exti4_15, &exti4_15_irq),
+	};
+
+	unsafe {
+		let _critical = sync::Critical::new();
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB2ENR_OFFSET, reg::RCC_APB2ENR_SYSCFGCOMPEN_MSK);
+		regop::write_mask(P::SYSCFG_EXTICR, exticr, 0b1111 << ((line % 4) * 4));
+		regop::write_mask(rising, reg::EXTI_BASE + reg::EXTI_RTSR_OFFSET, 1 << line);
+		regop::write_mask(falling, reg::EXTI_BASE + reg::EXTI_FTSR_OFFSET, 1 << line);
+		regop::write(1 << line, reg::EXTI_BASE + reg::EXTI_PR_OFFSET);  // Drop a stale request, if there is one
+		regop::write_mask(1, reg::EXTI_BASE + reg::EXTI_IMR_OFFSET, 1 << line);
+	}
+
+	irq::register(interrupt, handler);
+	nvic::set_priority(interrupt, nvic::PRIORITY_KERNEL);
+	nvic::enable(interrupt);
+
+	N
+}
+
+/// Masks the line's interrupt requests
+///
+pub fn disable(line: u8) {
+	unsafe {
+		let _critical = sync::Critical::new();
+		regop::write_mask(0, reg::EXTI_BASE + reg::EXTI_IMR_OFFSET, 1 << line);
+	}
+}
+
+/// Sets a function to be called from the ISR, when the line is triggered
+///
+pub fn set_callback(line: u8, callback: Option<Callback>) {
+	let _critical = sync::Critical::new();
+
+	unsafe {
+		CALLBACKS[line as usize] = callback;
+	}
+}
+
+/// Blocks the calling task until the line gets triggered. Triggers that have occurred since the last `wait` are
+/// not queued: if there was one, the call returns immediately
+///
+pub fn wait(line: u8) {
+	unsafe {
+		<sync::Sem as Lock>::lock(&mut SIGNALS[line as usize]);
+	}
+}
+
+/// Non-blocking version of `wait`. Returns true, if the line has been triggered
+///
+pub fn try_wait(line: u8) -> bool {
+	unsafe {
+		<sync::Sem as Lock>::try_lock(&mut SIGNALS[line as usize])
+	}
+}
+
+fn dispatch(lines: core::ops::RangeInclusive<usize>) {
+	unsafe {
+		let pending = regop::read(reg::EXTI_BASE + reg::EXTI_PR_OFFSET);
+
+		for line in lines.filter(|line| pending & (1 << line) != 0) {
+			regop::write(1 << line, reg::EXTI_BASE + reg::EXTI_PR_OFFSET);  // PR is cleared by writing 1
+
+			if let Some(callback) = CALLBACKS[line] {
+				callback(line as u8);
+			}
+
+			<sync::Sem as LockIsr>::unlock(&mut SIGNALS[line]);
+		}
+	}
+}
+
+fn exti0_1_irq() {
+	dispatch(0..=1);
+}
+
+fn exti2_3_irq() {
+	dispatch(2..=3);
+}
+
+fn exti4_15_irq() {
+	dispatch(4..=15);
+}
@@ -1,27 +1,230 @@
-use crate::{regop, reg};
+use crate::{regop, reg, thread::sync};
+use core::marker::PhantomData;
 
-pub fn configure() {
-	// GPIOB, PB8, Open-drain
-	const GPIO_MODER_OUTPUT: usize = 0b01;  // General purpose IO
-	const GPIO_MODER_ALTERNATE_FUNCTION: usize = 0b10;  // General purpose IO
-	const GPIO_OTYPER_OPENDRAIN: usize = 0b1;  // Output type - open-drain
-	const GPIO_OSPEEDR_HIGH: usize = 0b11;  // High speed output
-	const GPIO_PUPDR_NOPULL: usize = 0b00;  // No pull-up, no pull-down
-	const PA2_AF_USART1_TX: usize = 1;
-	const PA3_AF_USART1_RX: usize = 1;
-
-	// CubeMX deviates from its naming scheme here, so everything has to be set manually
-	unsafe {
-		// LED
-		regop::write_mask(GPIO_MODER_OUTPUT, reg::GPIOA_BASE + reg::GPIO_MODER_OFFSET, reg::GPIO_MODER_MODER4_MSK);
-		regop::write_mask(GPIO_OTYPER_OPENDRAIN, reg::GPIOA_BASE + reg::GPIO_OTYPER_OFFSET, reg::GPIO_OTYPER_OT_4);
-		regop::write_mask(GPIO_OSPEEDR_HIGH, reg::GPIOA_BASE + reg::GPIO_OSPEEDR_OFFSET, reg::GPIO_OSPEEDR_OSPEEDR4_MSK);
-		regop::write_mask(GPIO_PUPDR_NOPULL, reg::GPIOA_BASE + reg::GPIO_PUPDR_OFFSET, reg::GPIO_PUPDR_PUPDR4_MSK);
-		regop::write_mask(0, reg::GPIOA_BASE + reg::GPIO_BSRR_OFFSET, reg::GPIO_BSRR_BS_4);
-		// USART 1
-		regop::write_mask(GPIO_MODER_ALTERNATE_FUNCTION, reg::GPIOA_BASE + reg::GPIO_MODER_OFFSET, reg::GPIO_MODER_MODER2_MSK);  // AF mode, PA2 - TX
-		regop::write_mask(GPIO_MODER_ALTERNATE_FUNCTION, reg::GPIOA_BASE + reg::GPIO_MODER_OFFSET, reg::GPIO_MODER_MODER3_MSK);  // AF mode, PA3 - RX
-		regop::write_mask(PA2_AF_USART1_TX, reg::GPIOA_BASE + reg::GPIO_AFR_0_OFFSET, reg::GPIO_AFRL_AFRL2_MSK);  // PA2, alternative function # 1
-		regop::write_mask(PA3_AF_USART1_RX, reg::GPIOA_BASE + reg::GPIO_AFR_0_OFFSET, reg::GPIO_AFRL_AFRL3_MSK);  // PA3, alternative function # 1
+/// GPIO port, provides the register block's base address
+///
+pub trait Port {
+	const BASE: usize;
+	const RCC_AHBENR_EN_MSK: usize;
+	const SYSCFG_EXTICR: usize;  // Port selection code for EXTI lines
+}
+
+pub struct A;
+pub struct B;
+pub struct C;
+pub struct D;
+pub struct F;
+
+macro_rules! port_impl {
+	($port:ident, $base:ident, $en:ident, $exticr:expr) => {
+		impl Port for $port {
+			const BASE: usize = reg::$base;
+			const RCC_AHBENR_EN_MSK: usize = reg::$en;
+			const SYSCFG_EXTICR: usize = $exticr;
+		}
+	};
+}
+
+port_impl!(A, GPIOA_BASE, RCC_AHBENR_GPIOAEN_MSK, 0b0000);
+port_impl!(B, GPIOB_BASE, RCC_AHBENR_GPIOBEN_MSK, 0b0001);
+port_impl!(C, GPIOC_BASE, RCC_AHBENR_GPIOCEN_MSK, 0b0010);
+port_impl!(D, GPIOD_BASE, RCC_AHBENR_GPIODEN_MSK, 0b0011);
+port_impl!(F, GPIOF_BASE, RCC_AHBENR_GPIOFEN_MSK, 0b0101);
+
+/// Pull-up / pull-down configuration, PUPDR values
+///
+pub trait Pull {
+	const PUPDR: usize;
+}
+
+pub struct Floating;
+pub struct PullUp;
+pub struct PullDown;
+
+impl Pull for Floating { const PUPDR: usize = 0b00; }
+impl Pull for PullUp { const PUPDR: usize = 0b01; }
+impl Pull for PullDown { const PUPDR: usize = 0b10; }
+
+/// Output driver configuration, OTYPER values
+///
+pub trait OutputType {
+	const OTYPER: usize;
+}
+
+pub struct PushPull;
+pub struct OpenDrain;
+
+impl OutputType for PushPull { const OTYPER: usize = 0b0; }
+impl OutputType for OpenDrain { const OTYPER: usize = 0b1; }
+
+/// OSPEEDR values
+///
+pub enum Speed {
+	Low = 0b00,
+	Medium = 0b01,
+	High = 0b11,
+}
+
+// Pin modes
+
+/// The pin has not been configured through this API. Its state is whatever it is after reset or a previous use
+pub struct Unconfigured;
+pub struct Input<P: Pull>(PhantomData<P>);
+pub struct Output<T: OutputType>(PhantomData<T>);
+pub struct Alternate<const AF: u8, T: OutputType>(PhantomData<T>);
+pub struct Analog;
+
+/// Modes that drive the pin, so its speed is relevant
+///
+pub trait Driven {}
+impl<T: OutputType> Driven for Output<T> {}
+impl<const AF: u8, T: OutputType> Driven for Alternate<AF, T> {}
+
+const MODER_INPUT: usize = 0b00;
+const MODER_OUTPUT: usize = 0b01;
+const MODER_ALTERNATE: usize = 0b10;
+const MODER_ANALOG: usize = 0b11;
+
+/// Type-state pin handle. Zero-sized, the state is tracked by the type, so e.g. driving an input is a compile error.
+///
+/// Pins are not tracked at runtime, so it is up to the user not to take the same pin twice
+///
+pub struct Pin<P: Port, const N: u8, M> {
+	_p: PhantomData<(P, M)>,
+}
+
+impl<P: Port, const N: u8> Pin<P, N, Unconfigured> {
+	const VALID: () = assert!(N < 16, "GPIO pin number must be in range [0; 15]");
+
+	/// Takes a pin, and enables its port's clock
+	///
+	pub fn new() -> Self {
+		let _ = Self::VALID;
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(1, reg::RCC_BASE + reg::RCC_AHBENR_OFFSET, P::RCC_AHBENR_EN_MSK);
+		}
+
+		Self {_p: PhantomData}
+	}
+}
+
+impl<P: Port, const N: u8, M> Pin<P, N, M> {
+	/// Updates a field of a configuration register, where each pin takes `width` bits
+	///
+	fn configure(offset: usize, width: usize, val: usize) {
+		let mask = ((1 << width) - 1) << (N as usize * width);
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(val, P::BASE + offset, mask);
+		}
+	}
+
+	fn into_mode<T>(self, moder: usize) -> Pin<P, N, T> {
+		Self::configure(reg::GPIO_MODER_OFFSET, 2, moder);
+		Pin {_p: PhantomData}
 	}
+
+	pub fn into_input<U: Pull>(self) -> Pin<P, N, Input<U>> {
+		Self::configure(reg::GPIO_PUPDR_OFFSET, 2, U::PUPDR);
+		self.into_mode(MODER_INPUT)
+	}
+
+	pub fn into_output<T: OutputType>(self) -> Pin<P, N, Output<T>> {
+		Self::configure(reg::GPIO_OTYPER_OFFSET, 1, T::OTYPER);
+		Self::configure(reg::GPIO_PUPDR_OFFSET, 2, Floating::PUPDR);
+		self.into_mode(MODER_OUTPUT)
+	}
+
+	/// Routes the pin to a peripheral. See "Alternate functions" tables of the datasheet
+	///
+	pub fn into_alternate<const AF: u8, T: OutputType>(self) -> Pin<P, N, Alternate<AF, T>> {
+		let _ = Pin::<P, N, Alternate<AF, T>>::AF_VALID;
+
+		if N < 8 {
+			Self::configure(reg::GPIO_AFR_0_OFFSET, 4, AF as usize);
+		} else {
+			// AFRH, fields are indexed from pin 8
+			let mask = 0b1111 << ((N as usize - 8) * 4);
+
+			unsafe {
+				let _critical = sync::Critical::new();
+				regop::write_mask(AF as usize, P::BASE + reg::GPIO_AFR_1_OFFSET, mask);
+			}
+		}
+
+		Self::configure(reg::GPIO_OTYPER_OFFSET, 1, T::OTYPER);
+		self.into_mode(MODER_ALTERNATE)
+	}
+
+	pub fn into_analog(self) -> Pin<P, N, Analog> {
+		Self::configure(reg::GPIO_PUPDR_OFFSET, 2, Floating::PUPDR);
+		self.into_mode(MODER_ANALOG)
+	}
+
+	/// Input data register value. Valid in every mode but analog
+	///
+	pub fn is_high(&self) -> bool {
+		unsafe {
+			regop::read_mask(P::BASE + reg::GPIO_IDR_OFFSET, 1 << N) != 0
+		}
+	}
+
+	pub fn is_low(&self) -> bool {
+		!self.is_high()
+	}
+}
+
+impl<P: Port, const N: u8, const AF: u8, T: OutputType> Pin<P, N, Alternate<AF, T>> {
+	const AF_VALID: () = assert!(AF < 8, "STM32F030 has alternate functions AF0..AF7");
+}
+
+impl<P: Port, const N: u8, M: Driven> Pin<P, N, M> {
+	pub fn set_speed(&mut self, speed: Speed) {
+		Self::configure(reg::GPIO_OSPEEDR_OFFSET, 2, speed as usize);
+	}
+}
+
+impl<P: Port, const N: u8, T: OutputType> Pin<P, N, Output<T>> {
+	/// Atomic, as BSRR does not require read-modify-write
+	///
+	pub fn set_high(&mut self) {
+		unsafe {
+			regop::write(1 << N, P::BASE + reg::GPIO_BSRR_OFFSET);
+		}
+	}
+
+	pub fn set_low(&mut self) {
+		unsafe {
+			regop::write(1 << (N as usize + 16), P::BASE + reg::GPIO_BSRR_OFFSET);
+		}
+	}
+
+	pub fn is_set_high(&self) -> bool {
+		unsafe {
+			regop::read_mask(P::BASE + reg::GPIO_ODR_OFFSET, 1 << N) != 0
+		}
+	}
+
+	pub fn toggle(&mut self) {
+		if self.is_set_high() {
+			self.set_low();
+		} else {
+			self.set_high();
+		}
+	}
+}
+
+pub fn configure() {
+	// LED
+	let mut led = Pin::<A, 4, _>::new().into_output::<OpenDrain>();
+	led.set_speed(Speed::High);
+	led.set_low();
+
+	// USART 1
+	const AF_USART1: u8 = 1;
+	let _tx = Pin::<A, 2, _>::new().into_alternate::<AF_USART1, PushPull>();
+	let _rx = Pin::<A, 3, _>::new().into_alternate::<AF_USART1, PushPull>();
 }
@@ -0,0 +1,546 @@
+use crate::{regop, reg, tim, thread::sync, thread::sync::{Lock, LockIsr}, periph::{rcc, nvic, irq,
+	gpio::{self, Pin, Alternate, OpenDrain}}};
+use core::marker::PhantomData;
+
+/// I2C1 pins. Implemented for open-drain `Alternate` pins w/ the matching AF number, see "Alternate functions"
+/// tables of the datasheet. Provide the pin's location, so the bus recovery can drive it as a GPIO
+///
+pub trait SclPin {
+	const PORT: usize;
+	const N: usize;
+}
+
+pub trait SdaPin {
+	const PORT: usize;
+	const N: usize;
+}
+
+macro_rules! pin_impl {
+	($trait:ident, $port:ident, $n:expr, $af:expr) => {
+		impl $trait for Pin<gpio::$port, $n, Alternate<$af, OpenDrain>> {
+			const PORT: usize = <gpio::$port as gpio::Port>::BASE;
+			const N: usize = $n;
+		}
+	};
+}
+
+pin_impl!(SclPin, A, 9, 4);
+pin_impl!(SdaPin, A, 10, 4);
+pin_impl!(SclPin, B, 6, 1);
+pin_impl!(SdaPin, B, 7, 1);
+pin_impl!(SclPin, B, 8, 1);
+pin_impl!(SdaPin, B, 9, 1);
+
+#[derive(Clone, Copy)]
+pub enum Speed {
+	Standard,  // 100 kHz
+	Fast,  // 400 kHz
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+	Nack,  // The slave has not acknowledged its address, or a data byte
+	ArbitrationLoss,  // Another master has taken the bus
+	Bus,  // Misplaced START / STOP, or the bus is stuck
+	Timeout,  // E.g. a slave stretches the clock for too long
+}
+
+const TIMEOUT: tim::Duration = tim::Duration::from_millis(25);  // SMBus' clock low timeout
+const MAX_CHUNK: usize = 255;  // NBYTES is 8-bit, longer transfers are split w/ RELOAD
+const MODER_OUTPUT: usize = 0b01;
+const MODER_ALTERNATE: usize = 0b10;
+
+static mut BUS: sync::Sem = sync::Sem::new(1, 1);
+
+/// Computes TIMINGR for the I2C kernel clock. The timings are those of the reference manual's examples for 8 MHz,
+/// expressed in prescaled clock ticks, the prescaler is chosen so the tick is not shorter than in the examples
+///
+fn timingr(clock_hz: usize, speed: Speed) -> usize {
+	// (tick rate, SCLL, SCLH, SDADEL, SCLDEL)
+	let (tick_hz, scll, sclh, sdadel, scldel) = match speed {
+		Speed::Standard => (4_000_000, 0x13, 0xf, 0x2, 0x4),
+		Speed::Fast => (8_000_000, 0x9, 0x3, 0x1, 0x3),
+	};
+	let presc = clock_hz.div_ceil(tick_hz) - 1;
+	assert!(presc <= 0xf, "I2C clock is too fast for the timing prescaler");
+
+	(presc << reg::I2C_TIMINGR_PRESC_POS)
+		| (scldel << reg::I2C_TIMINGR_SCLDEL_POS)
+		| (sdadel << reg::I2C_TIMINGR_SDADEL_POS)
+		| (sclh << reg::I2C_TIMINGR_SCLH_POS)
+		| (scll << reg::I2C_TIMINGR_SCLL_POS)
+}
+
+enum Buffer<'a> {
+	Write(&'a [u8]),
+	Read(&'a mut [u8]),
+}
+
+impl Buffer<'_> {
+	fn len(&self) -> usize {
+		match self {
+			Buffer::Write(b) => b.len(),
+			Buffer::Read(b) => b.len(),
+		}
+	}
+}
+
+/// I2C1 master, 7-bit addressing. The handle is `Copy`, so each task may have its own, transactions are serialized
+/// by the bus lock
+///
+pub struct I2c<SCL: SclPin, SDA: SdaPin> {
+	_p: PhantomData<(SCL, SDA)>,
+}
+
+impl<SCL: SclPin, SDA: SdaPin> Clone for I2c<SCL, SDA> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<SCL: SclPin, SDA: SdaPin> Copy for I2c<SCL, SDA> {}
+
+impl<SCL: SclPin, SDA: SdaPin> I2c<SCL, SDA> {
+	/// The pins are taken to make sure they have been routed to I2C1. I2C1 is either a master, or a `Slave`
+	///
+	pub fn new(_scl: SCL, _sda: SDA, clocks: &rcc::Clocks, speed: Speed) -> Self {
+		enable(clocks, speed);
+
+		I2c {_p: PhantomData}
+	}
+
+	fn transaction(&self, f: impl FnOnce() -> Result<(), Error>) -> Result<(), Error> {
+		unsafe {
+			<sync::Sem as Lock>::lock(&mut BUS);
+		}
+
+		// A slave holding SDA low after a reset keeps the bus busy
+		let ret = match wait_idle() {
+			Err(_) => recover::<SCL, SDA>().and_then(|_| f()),
+			Ok(_) => f(),
+		};
+
+		if let Err(error) = ret {
+			abort(error);
+		}
+
+		unsafe {
+			<sync::Sem as Lock>::unlock(&mut BUS);
+		}
+
+		ret
+	}
+
+	pub fn write(&self, address: u8, bytes: &[u8]) -> Result<(), Error> {
+		self.transaction(|| transfer(address, Buffer::Write(bytes), true))
+	}
+
+	pub fn read(&self, address: u8, buffer: &mut [u8]) -> Result<(), Error> {
+		self.transaction(|| transfer(address, Buffer::Read(buffer), true))
+	}
+
+	/// Writes, and reads back after a repeated START, e.g. a register address followed by its value
+	///
+	pub fn write_read(&self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Error> {
+		self.transaction(|| {
+			transfer(address, Buffer::Write(bytes), false)?;
+			transfer(address, Buffer::Read(buffer), true)
+		})
+	}
+
+	/// Frees the bus from a slave stuck in the middle of a byte, e.g. after the master has been reset
+	///
+	pub fn recover(&self) -> Result<(), Error> {
+		unsafe {
+			<sync::Sem as Lock>::lock(&mut BUS);
+		}
+
+		let ret = recover::<SCL, SDA>();
+
+		unsafe {
+			<sync::Sem as Lock>::unlock(&mut BUS);
+		}
+
+		ret
+	}
+}
+
+/// Enables I2C1 clocked from SYSCLK, and sets the timings, the peripheral is left enabled w/ CR1 reset otherwise
+///
+fn enable(clocks: &rcc::Clocks, speed: Speed) {
+	unsafe {
+		let _critical = sync::Critical::new();
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB1ENR_OFFSET, reg::RCC_APB1ENR_I2C1EN_MSK);
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_CFGR3_OFFSET, reg::RCC_CFGR3_I2C1SW_MSK);
+	}
+
+	unsafe {
+		regop::write(0, reg::I2C1_BASE + reg::I2C_CR1_OFFSET);  // TIMINGR may only be changed while disabled
+		regop::write(timingr(clocks.sysclk, speed), reg::I2C1_BASE + reg::I2C_TIMINGR_OFFSET);
+		regop::write_mask(1, reg::I2C1_BASE + reg::I2C_CR1_OFFSET, reg::I2C_CR1_PE_MSK);
+	}
+}
+
+fn wait_idle() -> Result<(), Error> {
+	tim::wait_until(|| unsafe {regop::read_mask(reg::I2C1_BASE + reg::I2C_ISR_OFFSET, reg::I2C_ISR_BUSY_MSK)} == 0,
+		TIMEOUT).map_err(|_| Error::Bus)
+}
+
+/// Waits for an ISR flag, or an error
+///
+fn wait_flag(flag: usize) -> Result<(), Error> {
+	let mut error = None;
+
+	tim::wait_until(|| {
+		let isr = unsafe {regop::read(reg::I2C1_BASE + reg::I2C_ISR_OFFSET)};
+
+		error = if isr & reg::I2C_ISR_NACKF_MSK != 0 {
+			Some(Error::Nack)
+		} else if isr & reg::I2C_ISR_ARLO_MSK != 0 {
+			Some(Error::ArbitrationLoss)
+		} else if isr & reg::I2C_ISR_BERR_MSK != 0 {
+			Some(Error::Bus)
+		} else {
+			None
+		};
+
+		error.is_some() || isr & flag != 0
+	}, TIMEOUT).map_err(|_| Error::Timeout)?;
+
+	error.map_or(Ok(()), Err)
+}
+
+/// NBYTES, RELOAD, and AUTOEND for the next chunk of a transfer
+///
+fn chunk(remaining: usize, stop: bool) -> usize {
+	let nbytes = core::cmp::min(remaining, MAX_CHUNK) << reg::I2C_CR2_NBYTES_POS;
+
+	if remaining > MAX_CHUNK {
+		nbytes | reg::I2C_CR2_RELOAD_MSK
+	} else if stop {
+		nbytes | reg::I2C_CR2_AUTOEND_MSK
+	} else {
+		nbytes
+	}
+}
+
+/// Generates a (repeated) START, and transfers the buffer. `stop` - end w/ a STOP, otherwise the bus is held for
+/// the next transfer
+///
+fn transfer(address: u8, mut buffer: Buffer, stop: bool) -> Result<(), Error> {
+	let len = buffer.len();
+	let cr2 = reg::I2C1_BASE + reg::I2C_CR2_OFFSET;
+	let fields = reg::I2C_CR2_NBYTES_MSK | reg::I2C_CR2_RELOAD_MSK | reg::I2C_CR2_AUTOEND_MSK;
+
+	unsafe {
+		regop::write(((address as usize) << 1)
+			| if let Buffer::Read(_) = buffer {reg::I2C_CR2_RD_WRN_MSK} else {0}
+			| chunk(len, stop)
+			| reg::I2C_CR2_START_MSK, cr2);
+	}
+
+	for i in 0..len {
+		if i > 0 && i % MAX_CHUNK == 0 {
+			wait_flag(reg::I2C_ISR_TCR_MSK)?;
+
+			unsafe {
+				let val = regop::read(cr2) & !fields & !reg::I2C_CR2_START_MSK;
+				regop::write(val | chunk(len - i, stop), cr2);
+			}
+		}
+
+		match buffer {
+			Buffer::Write(bytes) => unsafe {
+				wait_flag(reg::I2C_ISR_TXIS_MSK)?;
+				regop::write(bytes[i] as usize, reg::I2C1_BASE + reg::I2C_TXDR_OFFSET);
+			},
+			Buffer::Read(ref mut bytes) => unsafe {
+				wait_flag(reg::I2C_ISR_RXNE_MSK)?;
+				bytes[i] = regop::read(reg::I2C1_BASE + reg::I2C_RXDR_OFFSET) as u8;
+			},
+		}
+	}
+
+	if stop {
+		wait_flag(reg::I2C_ISR_STOPF_MSK)?;
+
+		unsafe {
+			regop::write(reg::I2C_ICR_STOPCF_MSK, reg::I2C1_BASE + reg::I2C_ICR_OFFSET);
+		}
+
+		Ok(())
+	} else {
+		wait_flag(reg::I2C_ISR_TC_MSK)
+	}
+}
+
+/// Brings the peripheral back to idle after a failed transfer
+///
+fn abort(error: Error) {
+	let isr = reg::I2C1_BASE + reg::I2C_ISR_OFFSET;
+
+	unsafe {
+		match error {
+			Error::Nack => {
+				// W/o AUTOEND, the STOP is up to software
+				if regop::read_mask(isr, reg::I2C_ISR_STOPF_MSK) == 0 {
+					regop::write_mask(1, reg::I2C1_BASE + reg::I2C_CR2_OFFSET, reg::I2C_CR2_STOP_MSK);
+				}
+
+				let _ = tim::wait_until(|| regop::read_mask(isr, reg::I2C_ISR_STOPF_MSK) != 0, TIMEOUT);
+			},
+			Error::ArbitrationLoss => {},  // The bus is released by hardware
+			Error::Bus | Error::Timeout => reset(),
+		}
+
+		regop::write(reg::I2C_ICR_NACKCF_MSK | reg::I2C_ICR_STOPCF_MSK | reg::I2C_ICR_ARLOCF_MSK
+			| reg::I2C_ICR_BERRCF_MSK, reg::I2C1_BASE + reg::I2C_ICR_OFFSET);
+		regop::write(reg::I2C_ISR_TXE_MSK, isr);  // Flush TXDR
+	}
+}
+
+/// Software reset, returns the state machines to idle. PE must be kept low for at least 3 APB cycles, which the
+/// read back ensures
+///
+fn reset() {
+	let cr1 = reg::I2C1_BASE + reg::I2C_CR1_OFFSET;
+
+	unsafe {
+		regop::write_mask(0, cr1, reg::I2C_CR1_PE_MSK);
+
+		while regop::read_mask(cr1, reg::I2C_CR1_PE_MSK) != 0 {}
+
+		regop::write_mask(1, cr1, reg::I2C_CR1_PE_MSK);
+	}
+}
+
+fn set_moder(port: usize, n: usize, moder: usize) {
+	unsafe {
+		let _critical = sync::Critical::new();
+		regop::write_mask(moder, port + reg::GPIO_MODER_OFFSET, 0b11 << (n * 2));
+	}
+}
+
+fn set_pin(port: usize, n: usize, high: bool) {
+	unsafe {
+		regop::write(if high {1 << n} else {1 << (n + 16)}, port + reg::GPIO_BSRR_OFFSET);
+	}
+}
+
+fn pin_high(port: usize, n: usize) -> bool {
+	unsafe {
+		regop::read_mask(port + reg::GPIO_IDR_OFFSET, 1 << n) != 0
+	}
+}
+
+/// Clocks SCL by hand, as GPIO, until the slave releases SDA, up to 9 pulses, i.e. the rest of a byte, and its
+/// ACK. Then generates a STOP, and hands the pins back to the peripheral
+///
+fn recover<SCL: SclPin, SDA: SdaPin>() -> Result<(), Error> {
+	const HALF_PERIOD: u32 = 5;  // us, 100 kHz
+
+	unsafe {
+		regop::write_mask(0, reg::I2C1_BASE + reg::I2C_CR1_OFFSET, reg::I2C_CR1_PE_MSK);
+	}
+
+	// The pins are open-drain already, so driving them high releases the lines
+	set_pin(SCL::PORT, SCL::N, true);
+	set_pin(SDA::PORT, SDA::N, true);
+	set_moder(SCL::PORT, SCL::N, MODER_OUTPUT);
+	set_moder(SDA::PORT, SDA::N, MODER_OUTPUT);
+
+	for _ in 0..9 {
+		if pin_high(SDA::PORT, SDA::N) {
+			break;
+		}
+
+		set_pin(SCL::PORT, SCL::N, false);
+		tim::delay_us(HALF_PERIOD);
+		set_pin(SCL::PORT, SCL::N, true);
+		tim::delay_us(HALF_PERIOD);
+	}
+
+	// STOP: SDA rises while SCL is high
+	set_pin(SCL::PORT, SCL::N, false);
+	set_pin(SDA::PORT, SDA::N, false);
+	tim::delay_us(HALF_PERIOD);
+	set_pin(SCL::PORT, SCL::N, true);
+	tim::delay_us(HALF_PERIOD);
+	set_pin(SDA::PORT, SDA::N, true);
+	tim::delay_us(HALF_PERIOD);
+
+	let released = pin_high(SDA::PORT, SDA::N) && pin_high(SCL::PORT, SCL::N);
+
+	set_moder(SCL::PORT, SCL::N, MODER_ALTERNATE);
+	set_moder(SDA::PORT, SDA::N, MODER_ALTERNATE);
+
+	unsafe {
+		regop::write_mask(1, reg::I2C1_BASE + reg::I2C_CR1_OFFSET, reg::I2C_CR1_PE_MSK);
+	}
+
+	if released {Ok(())} else {Err(Error::Bus)}
+}
+
+/// Register-map style slave. The first byte of a master's write selects the register, the following ones are written
+/// into it, and the next ones, as the register address is incremented after each byte. A master's read starts at
+/// the selected register, e.g. after a write of just the register address, and increments it the same way
+///
+/// Both functions are called from the ISR, while the clock is stretched, so they should be short
+///
+#[derive(Clone, Copy)]
+pub struct Registers {
+	pub read: fn(register: u8) -> u8,
+	pub write: fn(register: u8, value: u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+	Write,  // Master to slave
+	Read,  // Slave to master
+}
+
+/// A completed master's transaction, as seen by the slave
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Event {
+	pub direction: Direction,
+	pub register: u8,  // The first register accessed
+	pub len: u8,  // Number of data bytes, the register address excluded. Wraps at 256
+}
+
+struct SlaveState {
+	registers: Option<Registers>,
+	register: u8,  // Next register to be accessed
+	selecting: bool,  // The next written byte is a register address
+	event: Event,  // The transaction in progress
+	pending: Option<Event>,  // The last completed transaction, not yet taken by the task
+}
+
+static mut SLAVE: SlaveState = SlaveState {registers: None, register: 0, selecting: false,
+	event: Event {direction: Direction::Write, register: 0, len: 0}, pending: None};
+static mut EVENT: sync::Sem = sync::Sem::new(0, 1);
+
+/// I2C1 slave w/ a 7-bit own address. Clock stretching is enabled, so the master waits while the callbacks run
+///
+pub struct Slave {
+	_p: (),
+}
+
+impl Slave {
+	pub fn new<SCL: SclPin, SDA: SdaPin>(_scl: SCL, _sda: SDA, clocks: &rcc::Clocks, speed: Speed, address: u8,
+		registers: Registers) -> Self {
+		enable(clocks, speed);
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			SLAVE.registers = Some(registers);
+		}
+
+		irq::register(nvic::Interrupt::I2c1, &slave_isr);
+
+		unsafe {
+			regop::write(0, reg::I2C1_BASE + reg::I2C_OAR1_OFFSET);  // OA1 may only be changed while disabled
+			regop::write(((address as usize) << 1) | reg::I2C_OAR1_OA1EN_MSK, reg::I2C1_BASE + reg::I2C_OAR1_OFFSET);
+			regop::write(regop::read(reg::I2C1_BASE + reg::I2C_CR1_OFFSET)
+				| reg::I2C_CR1_ADDRIE_MSK
+				| reg::I2C_CR1_RXIE_MSK
+				| reg::I2C_CR1_TXIE_MSK
+				| reg::I2C_CR1_STOPIE_MSK
+				| reg::I2C_CR1_NACKIE_MSK
+				| reg::I2C_CR1_ERRIE_MSK, reg::I2C1_BASE + reg::I2C_CR1_OFFSET);
+		}
+
+		nvic::set_priority(nvic::Interrupt::I2c1, nvic::PRIORITY_KERNEL);
+		nvic::enable(nvic::Interrupt::I2c1);
+
+		Slave {_p: ()}
+	}
+
+	/// Blocks the task until a master's transaction is complete. If several complete in the meantime, only the last
+	/// one is reported
+	///
+	pub fn wait(&self) -> Event {
+		loop {
+			unsafe {
+				<sync::Sem as Lock>::lock(&mut EVENT);
+				let _critical = sync::Critical::new();
+
+				if let Some(event) = SLAVE.pending.take() {
+					return event;
+				}
+			}
+		}
+	}
+
+	/// Non-blocking `wait`
+	///
+	pub fn try_wait(&self) -> Option<Event> {
+		unsafe {
+			<sync::Sem as Lock>::try_lock(&mut EVENT);
+			let _critical = sync::Critical::new();
+
+			SLAVE.pending.take()
+		}
+	}
+}
+
+fn slave_isr() {
+	unsafe {
+		let isr = regop::read(reg::I2C1_BASE + reg::I2C_ISR_OFFSET);
+		let icr = reg::I2C1_BASE + reg::I2C_ICR_OFFSET;
+		let state = &mut SLAVE;
+		let registers = match state.registers {
+			Some(registers) => registers,
+			None => return,
+		};
+
+		if isr & (reg::I2C_ISR_BERR_MSK | reg::I2C_ISR_ARLO_MSK | reg::I2C_ISR_OVR_MSK) != 0 {
+			regop::write(reg::I2C_ICR_BERRCF_MSK | reg::I2C_ICR_ARLOCF_MSK | reg::I2C_ICR_OVRCF_MSK, icr);
+		}
+
+		if isr & reg::I2C_ISR_ADDR_MSK != 0 {
+			let direction = if isr & reg::I2C_ISR_DIR_MSK != 0 {Direction::Read} else {Direction::Write};
+			state.selecting = direction == Direction::Write;
+			state.event = Event {direction, register: state.register, len: 0};
+
+			if direction == Direction::Read {
+				regop::write(reg::I2C_ISR_TXE_MSK, reg::I2C1_BASE + reg::I2C_ISR_OFFSET);  // Flush a stale byte
+			}
+
+			regop::write(reg::I2C_ICR_ADDRCF_MSK, icr);  // Releases the clock
+		}
+
+		if isr & reg::I2C_ISR_RXNE_MSK != 0 {
+			let byte = regop::read(reg::I2C1_BASE + reg::I2C_RXDR_OFFSET) as u8;
+
+			if state.selecting {
+				state.selecting = false;
+				state.register = byte;
+				state.event.register = byte;
+			} else {
+				(registers.write)(state.register, byte);
+				state.register = state.register.wrapping_add(1);
+				state.event.len = state.event.len.wrapping_add(1);
+			}
+		}
+
+		if isr & reg::I2C_ISR_TXIS_MSK != 0 {
+			regop::write((registers.read)(state.register) as usize, reg::I2C1_BASE + reg::I2C_TXDR_OFFSET);
+			state.register = state.register.wrapping_add(1);
+			state.event.len = state.event.len.wrapping_add(1);
+		}
+
+		if isr & reg::I2C_ISR_NACKF_MSK != 0 {
+			// The master ends a read by NACKing the last byte. The byte loaded after it has not been sent
+			regop::write(reg::I2C_ICR_NACKCF_MSK, icr);
+			state.register = state.register.wrapping_sub(1);
+			state.event.len = state.event.len.wrapping_sub(1);
+		}
+
+		if isr & reg::I2C_ISR_STOPF_MSK != 0 {
+			regop::write(reg::I2C_ICR_STOPCF_MSK, icr);
+			state.pending = Some(state.event);
+			<sync::Sem as LockIsr>::unlock(&mut EVENT);
+		}
+	}
+}
@@ -0,0 +1,78 @@
+use crate::{regop, reg, periph::nvic, thread::sync};
+use core::arch::asm;
+
+/// Number of IRQ vectors. STM32F030 uses up to 30 of them, the rest are reserved, but served all the same
+///
+pub const NIRQS: usize = 32;
+
+/// Exception number of IRQ #0, see "Vector table" in the reference manual
+///
+const IRQ_EXCEPTION_OFFSET: usize = 16;
+
+/// An IRQ handler. Both `fn` items and closures qualify, e.g. `irq::register(Interrupt::Tim3, &on_tim3)`. A closure
+/// must outlive the registration, so it has to be put in a `static`, unless it captures nothing
+///
+pub type Handler = &'static dyn Fn();
+
+static mut HANDLERS: [Option<Handler>; NIRQS] = [None; NIRQS];
+
+/// Installs the interrupt's handler, replacing the previous one, if any. It does not enable the interrupt, see
+/// `nvic::enable`
+///
+pub fn register(irq: nvic::Interrupt, handler: Handler) {
+	let _critical = sync::Critical::new();
+
+	unsafe {
+		HANDLERS[irq as usize] = Some(handler);
+	}
+}
+
+/// Disables the interrupt, and removes its handler
+///
+pub fn unregister(irq: nvic::Interrupt) {
+	nvic::disable(irq);
+	let _critical = sync::Critical::new();
+
+	unsafe {
+		HANDLERS[irq as usize] = None;
+	}
+}
+
+pub fn is_registered(irq: nvic::Interrupt) -> bool {
+	unsafe {
+		HANDLERS[irq as usize].is_some()
+	}
+}
+
+/// Returns the number of the exception being served. 0 in Thread mode
+///
+pub fn active_exception() -> usize {
+	let ipsr: usize;
+
+	unsafe {
+		asm!("mrs {0}, IPSR", out(reg) ipsr, options(nomem, nostack));
+	}
+
+	ipsr & 0x3f
+}
+
+/// Common entry point of every IRQ vector, see `EXCEPTIONS` in "init.rs". Looks up the active IRQ's handler.
+///
+/// An IRQ w/o a handler is reported, and disabled, so it does not keep preempting the application
+///
+#[no_mangle]
+pub extern "C" fn irq_dispatch() {
+	let n = active_exception() - IRQ_EXCEPTION_OFFSET;
+
+	match unsafe {HANDLERS[n]} {
+		Some(handler) => handler(),
+		None => {
+			crate::error!("Unhandled IRQ {}", n);
+
+			unsafe {
+				// The number comes from hardware, and may not map onto `nvic::Interrupt`, hence raw access
+				regop::write(1 << n, reg::NVIC_BASE + reg::NVIC_ICER_0_OFFSET);
+			}
+		},
+	}
+}
@@ -0,0 +1,62 @@
+use crate::{regop, reg, tim, periph::rcc};
+use core::ptr;
+
+/// LSI, which clocks the watchdog, is an RC oscillator. Its frequency varies between parts in [30; 50] kHz, so the
+/// actual timeout may differ from the configured one by that much
+///
+const LSI_HZ: u64 = 40_000;
+const RLR_MAX: u64 = 0xfff;
+
+// KR keys
+const KEY_START: usize = 0xcccc;
+const KEY_RELOAD: usize = 0xaaaa;
+const KEY_UNLOCK: usize = 0x5555;  // Enables writes to PR, RLR
+
+// LSI's startup, and the update of PR, RLR take a few hundred us at most
+const TIMEOUT: tim::Duration = tim::Duration::from_millis(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+	Timeout,  // PR, RLR have not been updated in time, e.g. because LSI has not started
+}
+
+/// Starts the independent watchdog, which resets the device unless `kick` is called at least once per `timeout`.
+/// [0.1 ms; 26 s]. Once started, it cannot be stopped but by a reset. It is paused while the core is halted by a
+/// debugger. On `Error::Timeout`, the watchdog is running, but w/ an unknown timeout
+///
+pub fn start(timeout: tim::Duration) -> Result<(), Error> {
+	// The prescaler is 4 * 2^PR
+	let cycles = timeout.as_micros().saturating_mul(LSI_HZ) / 1_000_000;
+	let pr = (0..7).find(|pr| cycles / (4 << pr) <= RLR_MAX + 1).unwrap_or(6);
+	let rlr = (cycles / (4 << pr)).clamp(1, RLR_MAX + 1) - 1;
+
+	unsafe {
+		regop::write_mask(1, reg::DBGMCU_BASE + reg::DBGMCU_APB1FZ_OFFSET, reg::DBGMCU_APB1_FZ_DBG_IWDG_STOP_MSK);
+		regop::write(KEY_START, reg::IWDG_BASE + reg::IWDG_KR_OFFSET);  // Also starts LSI
+		regop::write(KEY_UNLOCK, reg::IWDG_BASE + reg::IWDG_KR_OFFSET);
+		regop::write(pr as usize, reg::IWDG_BASE + reg::IWDG_PR_OFFSET);
+		regop::write(rlr as usize, reg::IWDG_BASE + reg::IWDG_RLR_OFFSET);
+
+		// The values are transferred into the LSI domain, which takes several of its cycles
+		let sr = (reg::IWDG_BASE + reg::IWDG_SR_OFFSET) as *const usize;
+		let updated = tim::wait_until(|| ptr::read_volatile(sr) & (reg::IWDG_SR_PVU_MSK | reg::IWDG_SR_RVU_MSK) == 0, TIMEOUT);
+
+		regop::write(KEY_RELOAD, reg::IWDG_BASE + reg::IWDG_KR_OFFSET);
+
+		updated.map_err(|_| Error::Timeout)
+	}
+}
+
+/// Reloads the counter
+///
+pub fn kick() {
+	unsafe {
+		regop::write(KEY_RELOAD, reg::IWDG_BASE + reg::IWDG_KR_OFFSET);
+	}
+}
+
+/// Whether the last reset has been caused by the watchdog, see `rcc::reset_flags`
+///
+pub fn caused_reset() -> bool {
+	rcc::reset_flags().iwdg()
+}
@@ -2,5 +2,19 @@ pub mod rcc;
 pub mod gpio;
 pub mod usart;
 pub mod systick;
-pub mod tim14;
+pub mod timer;
+pub mod pwm;
+pub mod capture;
+pub mod dma;
+pub mod spi;
+pub mod i2c;
+pub mod adc;
+pub mod iwdg;
+pub mod wwdg;
+pub mod pwr;
+pub mod rtc;
+pub mod crc;
 pub mod pendsv;
+pub mod exti;
+pub mod nvic;
+pub mod irq;
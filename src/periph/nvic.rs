@@ -0,0 +1,234 @@
+use crate::{regop, reg, thread::sync};
+use core::arch::asm;
+
+/// STM32F030 interrupt vectors (IRQ numbers). Those marked w/ "x8/xC" are only present on the larger parts
+///
+#[derive(Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Interrupt {
+	Wwdg = 0,
+	Rtc = 2,
+	Flash = 3,
+	Rcc = 4,
+	Exti0_1 = 5,
+	Exti2_3 = 6,
+	Exti4_15 = 7,
+	Dma1Ch1 = 9,
+	Dma1Ch2_3 = 10,
+	Dma1Ch4_5 = 11,
+	Adc = 12,
+	Tim1BrkUpTrgCom = 13,
+	Tim1Cc = 14,
+	Tim3 = 16,
+	Tim6 = 17,  // x8/xC
+	Tim14 = 19,
+	Tim15 = 20,  // x8/xC
+	Tim16 = 21,
+	Tim17 = 22,
+	I2c1 = 23,
+	I2c2 = 24,  // x8/xC
+	Spi1 = 25,
+	Spi2 = 26,  // x8/xC
+	Usart1 = 27,
+	Usart2 = 28,  // x8/xC
+	Usart3_4_5_6 = 29,  // xC
+}
+
+/// System exceptions w/ configurable priority
+///
+#[derive(Clone, Copy)]
+pub enum SystemHandler {
+	SvCall,
+	PendSv,
+	SysTick,
+}
+
+// Cortex-M0 implements 2 priority bits (`__NVIC_PRIO_BITS`), so there are 4 levels. The lower the value, the higher
+// the priority
+pub const PRIORITY_HIGHEST: u8 = 0;
+pub const PRIORITY_LOWEST: u8 = (1 << reg::__NVIC_PRIO_BITS) - 1;
+
+/// Interrupts of this priority and lower may use kernel services (e.g. release a `Sem`), and are masked by
+/// `KernelMask`. Those above it must not touch the kernel, but are never delayed by it. SysTick runs on this level,
+/// and is masked as well, while PendSV has the lowest priority, so a context switch never preempts an ISR
+///
+pub const PRIORITY_KERNEL: u8 = 1;
+
+fn barrier() {
+	unsafe {
+		asm!("dsb", "isb", options(nostack, preserves_flags));
+	}
+}
+
+fn bit(irq: Interrupt) -> usize {
+	1 << irq as usize
+}
+
+pub fn enable(irq: Interrupt) {
+	unsafe {
+		regop::write(bit(irq), reg::NVIC_BASE + reg::NVIC_ISER_0_OFFSET);  // Write-1-to-set, no RMW required
+	}
+}
+
+/// Disables the interrupt. Upon return, its handler is guaranteed not to be invoked
+///
+pub fn disable(irq: Interrupt) {
+	unsafe {
+		let _critical = sync::Critical::new();
+		KERNEL_MASKED &= !bit(irq);  // Stays disabled after a `KernelMask` is dropped
+		regop::write(bit(irq), reg::NVIC_BASE + reg::NVIC_ICER_0_OFFSET);
+	}
+	barrier();
+}
+
+pub fn is_enabled(irq: Interrupt) -> bool {
+	unsafe {
+		regop::read(reg::NVIC_BASE + reg::NVIC_ISER_0_OFFSET) & bit(irq) != 0
+	}
+}
+
+/// Requests the interrupt in software
+///
+pub fn pend(irq: Interrupt) {
+	unsafe {
+		regop::write(bit(irq), reg::NVIC_BASE + reg::NVIC_ISPR_0_OFFSET);
+	}
+}
+
+pub fn unpend(irq: Interrupt) {
+	unsafe {
+		regop::write(bit(irq), reg::NVIC_BASE + reg::NVIC_ICPR_0_OFFSET);
+	}
+}
+
+pub fn is_pending(irq: Interrupt) -> bool {
+	unsafe {
+		regop::read(reg::NVIC_BASE + reg::NVIC_ISPR_0_OFFSET) & bit(irq) != 0
+	}
+}
+
+/// Returns (register address, field mask) of a priority byte. Only the upper `__NVIC_PRIO_BITS` bits of the byte
+/// are implemented
+///
+fn priority_field(register: usize, byte: usize) -> (usize, usize) {
+	const PRIO_SHIFT: usize = 8 - reg::__NVIC_PRIO_BITS;
+	(register, (PRIORITY_LOWEST as usize) << (byte * 8 + PRIO_SHIFT))
+}
+
+fn irq_priority_field(irq: Interrupt) -> (usize, usize) {
+	let n = irq as usize;
+	priority_field(reg::NVIC_BASE + reg::NVIC_IP_0_OFFSET + (n / 4) * 4, n % 4)
+}
+
+fn system_priority_field(handler: SystemHandler) -> (usize, usize) {
+	match handler {
+		SystemHandler::SvCall => priority_field(reg::SCB_BASE + reg::SCB_SHP_0_OFFSET, 3),
+		SystemHandler::PendSv => priority_field(reg::SCB_BASE + reg::SCB_SHP_1_OFFSET, 2),
+		SystemHandler::SysTick => priority_field(reg::SCB_BASE + reg::SCB_SHP_1_OFFSET, 3),
+	}
+}
+
+/// Priority registers only support word access on Cortex-M0 (unlike M3+), hence read-modify-write
+///
+fn set_priority_field((register, mask): (usize, usize), priority: u8) {
+	let _critical = sync::Critical::new();
+
+	unsafe {
+		regop::write_mask(core::cmp::min(priority, PRIORITY_LOWEST) as usize, register, mask);
+	}
+}
+
+fn get_priority_field((register, mask): (usize, usize)) -> u8 {
+	unsafe {
+		regop::read_mask(register, mask) as u8
+	}
+}
+
+/// Sets priority in range [PRIORITY_HIGHEST; PRIORITY_LOWEST]. Greater values are clamped
+///
+pub fn set_priority(irq: Interrupt, priority: u8) {
+	set_priority_field(irq_priority_field(irq), priority);
+}
+
+pub fn priority(irq: Interrupt) -> u8 {
+	get_priority_field(irq_priority_field(irq))
+}
+
+pub fn set_system_priority(handler: SystemHandler, priority: u8) {
+	set_priority_field(system_priority_field(handler), priority);
+}
+
+pub fn system_priority(handler: SystemHandler) -> u8 {
+	get_priority_field(system_priority_field(handler))
+}
+
+/// IRQs masked by the live `KernelMask`s, and not disabled since
+///
+static mut KERNEL_MASKED: usize = 0;
+
+/// RAII mask of the IRQs that may use kernel services (see `PRIORITY_KERNEL`), and SysTick. Unlike
+/// `sync::Critical`, it leaves higher priority IRQs, and PendSV enabled.
+///
+/// Cortex-M0 has no BASEPRI, so the IRQs are masked one by one through ICER, and SysTick through its TICKINT. They
+/// are restored on drop, except those `disable`d in the meantime. Interrupts requested while masked stay pending, and
+/// are served after the mask is dropped. A tick is not latched w/ TICKINT clear, so it is re-pended on drop, the mask
+/// should not be held for longer than a tick though, or the others are lost
+///
+pub struct KernelMask {
+	masked: usize,
+	tick: bool,  // TICKINT has been cleared by this mask
+}
+
+impl KernelMask {
+	pub fn new() -> KernelMask {
+		let _critical = sync::Critical::new();
+		let ctrl = reg::SYSTICK_BASE + reg::SYSTICK_CTRL_OFFSET;
+		let tick = system_priority(SystemHandler::SysTick) >= PRIORITY_KERNEL
+			&& unsafe {regop::read_mask(ctrl, reg::SYSTICK_CTRL_TICKINT_MSK)} != 0;
+
+		if tick {
+			unsafe {
+				core::ptr::read_volatile(ctrl as *const usize);  // Clears COUNTFLAG, so it reports wraps while masked only
+				regop::write_mask(0, ctrl, reg::SYSTICK_CTRL_TICKINT_MSK);
+			}
+		}
+
+		let enabled = unsafe {regop::read(reg::NVIC_BASE + reg::NVIC_ISER_0_OFFSET)};
+		let masked = (0..32)
+			.filter(|n| enabled & (1 << n) != 0)
+			.filter(|n| {
+				let (register, mask) = priority_field(reg::NVIC_BASE + reg::NVIC_IP_0_OFFSET + (n / 4) * 4, n % 4);
+				unsafe {regop::read_mask(register, mask) as u8 >= PRIORITY_KERNEL}
+			})
+			.fold(0, |acc, n| acc | (1 << n));
+
+		unsafe {
+			KERNEL_MASKED |= masked;
+			regop::write(masked, reg::NVIC_BASE + reg::NVIC_ICER_0_OFFSET);
+		}
+		barrier();
+
+		KernelMask {masked, tick}
+	}
+}
+
+impl Drop for KernelMask {
+	fn drop(&mut self) {
+		let _critical = sync::Critical::new();
+
+		unsafe {
+			regop::write(self.masked & KERNEL_MASKED, reg::NVIC_BASE + reg::NVIC_ISER_0_OFFSET);
+			KERNEL_MASKED &= !self.masked;
+
+			if self.tick {
+				let ctrl = reg::SYSTICK_BASE + reg::SYSTICK_CTRL_OFFSET;
+				let val = core::ptr::read_volatile(ctrl as *const usize);  // Clears COUNTFLAG, so it is read once
+				regop::write(val | reg::SYSTICK_CTRL_TICKINT_MSK, ctrl);
+
+				if val & reg::SYSTICK_CTRL_COUNTFLAG_MSK != 0 {
+					regop::write(reg::SCB_ICSR_PENDSTSET_MSK, reg::SCB_BASE + reg::SCB_ICSR_OFFSET);
+				}
+			}
+		}
+	}
+}
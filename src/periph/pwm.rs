@@ -0,0 +1,177 @@
+use crate::{regop, reg, thread::sync, periph::{rcc, timer::{Timer, Instance, ChannelPin, ComplementaryPin}}};
+use core::marker::PhantomData;
+
+#[derive(Clone, Copy)]
+pub enum Polarity {
+	ActiveHigh,
+	ActiveLow,
+}
+
+const OC_MODE_PWM1: usize = 0b110;  // Active while CNT < CCR
+
+/// PWM generator. All the channels of a timer share its frequency
+///
+pub struct Pwm<T: Instance> {
+	timer: Timer<T>,
+}
+
+impl<T: Instance> Pwm<T> {
+	/// Duty cycle resolution is `timer.tick_hz() / frequency_hz` steps, so the timer's tick rate should be chosen
+	/// accordingly
+	///
+	pub fn new(timer: Timer<T>, frequency_hz: usize) -> Self {
+		let mut pwm = Pwm {timer};
+		pwm.set_frequency(frequency_hz);
+
+		if T::ADVANCED {
+			unsafe {
+				regop::write_mask(1, T::BASE + reg::TIM_BDTR_OFFSET, reg::TIM_BDTR_MOE_MSK);  // Main output enable
+			}
+		}
+
+		pwm
+	}
+
+	/// Changes the period, duty cycles set in ticks are left as is
+	///
+	pub fn set_frequency(&mut self, frequency_hz: usize) {
+		let period = self.timer.tick_hz() / frequency_hz;
+		assert!(period >= 2 && period <= 0xffff, "PWM frequency is out of the timer's range");
+
+		unsafe {
+			regop::write(period - 1, T::BASE + reg::TIM_ARR_OFFSET);
+
+			if !self.timer.is_running() {
+				regop::write(reg::TIM_EGR_UG_MSK, T::BASE + reg::TIM_EGR_OFFSET);
+				regop::write_mask(1, T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_CEN_MSK);
+			}
+		}
+	}
+
+	/// Number of ticks in a period, i.e. the duty cycle value for 100%
+	///
+	pub fn max_duty(&self) -> u16 {
+		unsafe {
+			(regop::read(T::BASE + reg::TIM_ARR_OFFSET) + 1) as u16
+		}
+	}
+
+	/// Configures the channel in PWM mode 1 w/ 0% duty cycle, and routes it to the pin
+	///
+	pub fn channel<const C: u8, P: ChannelPin<T, C>>(&mut self, _pin: P) -> Channel<T, C> {
+		let _ = Channel::<T, C>::VALID;
+		let channel = Channel {_t: PhantomData};
+		channel.set_duty_ticks(0);
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			let (ccmr, shift) = Channel::<T, C>::ccmr();
+			regop::write_mask(OC_MODE_PWM1, ccmr, reg::TIM_CCMR1_OC1M_MSK << shift);
+			regop::write_mask(1, ccmr, reg::TIM_CCMR1_OC1PE_MSK << shift);  // Preload CCR, so duty changes are glitch-free
+		}
+
+		channel.enable();
+		channel
+	}
+}
+
+impl<T: Instance> Pwm<T> {
+	const HAS_DEAD_TIME: () = assert!(T::ADVANCED, "Only TIM1, TIM16, and TIM17 have dead-time generators");
+
+	/// Sets delay between deactivating one output of a complementary pair, and activating the other. Rounded down to
+	/// the closest value the generator supports, up to 1008 timer clock cycles
+	///
+	pub fn set_dead_time(&mut self, dead_time_ns: usize) {
+		let _ = Self::HAS_DEAD_TIME;
+		// 64-bit, as the product overflows above ~89 us at 48 MHz. Anything over the range saturates to 0xff
+		let cycles = (rcc::clocks().timclk as u64 * dead_time_ns as u64 / 1_000_000_000).min(1024) as usize;
+
+		// DTG encoding, see TIMx_BDTR in the reference manual
+		let dtg = match cycles {
+			0..=127 => cycles,
+			128..=255 => 0b1000_0000 | (cycles / 2 - 64),
+			256..=511 => 0b1100_0000 | (cycles / 8 - 32),
+			512..=1023 => 0b1110_0000 | (cycles / 16 - 32),
+			_ => 0xff,
+		};
+
+		unsafe {
+			regop::write_mask(dtg, T::BASE + reg::TIM_BDTR_OFFSET, reg::TIM_BDTR_DTG_MSK);
+		}
+	}
+}
+
+/// A PWM channel #C of timer T
+///
+pub struct Channel<T: Instance, const C: u8> {
+	_t: PhantomData<T>,
+}
+
+impl<T: Instance, const C: u8> Channel<T, C> {
+	const VALID: () = assert!(C >= 1 && C <= 4, "Timers have channels 1..4");
+
+	/// Returns (CCMR register, field shift). Channels 1, 2 are configured in CCMR1, 3, 4 in CCMR2
+	///
+	fn ccmr() -> (usize, usize) {
+		let offset = if C <= 2 {reg::TIM_CCMR1_OFFSET} else {reg::TIM_CCMR2_OFFSET};
+		(T::BASE + offset, ((C as usize - 1) % 2) * 8)
+	}
+
+	/// CCER field of the channel
+	///
+	fn ccer(mask: usize) -> usize {
+		mask << ((C as usize - 1) * 4)
+	}
+
+	fn write_ccer(&self, val: usize, mask: usize) {
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(val, T::BASE + reg::TIM_CCER_OFFSET, Self::ccer(mask));
+		}
+	}
+
+	pub fn enable(&self) {
+		self.write_ccer(1, reg::TIM_CCER_CC1E_MSK);
+	}
+
+	pub fn disable(&self) {
+		self.write_ccer(0, reg::TIM_CCER_CC1E_MSK);
+	}
+
+	pub fn set_polarity(&self, polarity: Polarity) {
+		self.write_ccer(polarity as usize, reg::TIM_CCER_CC1P_MSK);
+	}
+
+	/// Number of ticks the output is active for within a period, see `Pwm::max_duty`. Takes effect at the next period
+	///
+	pub fn set_duty_ticks(&self, ticks: u16) {
+		unsafe {
+			regop::write(ticks as usize, T::BASE + reg::TIM_CCR1_OFFSET + (C as usize - 1) * 4);
+		}
+	}
+
+	pub fn duty_ticks(&self) -> u16 {
+		unsafe {
+			regop::read(T::BASE + reg::TIM_CCR1_OFFSET + (C as usize - 1) * 4) as u16
+		}
+	}
+
+	/// Duty cycle in percent, [0; 100]. Greater values are clamped
+	///
+	pub fn set_duty_percent(&self, percent: u8) {
+		let max = unsafe {regop::read(T::BASE + reg::TIM_ARR_OFFSET) + 1};
+		let percent = core::cmp::min(percent, 100) as usize;
+		self.set_duty_ticks((max * percent / 100) as u16);
+	}
+
+	/// Enables the inverted output, see `Pwm::set_dead_time`
+	///
+	pub fn enable_complementary<P: ComplementaryPin<T, C>>(&self, _pin: P, polarity: Polarity) {
+		self.write_ccer(polarity as usize, reg::TIM_CCER_CC1NP_MSK);
+		self.write_ccer(1, reg::TIM_CCER_CC1NE_MSK);
+	}
+
+	pub fn disable_complementary(&self) {
+		self.write_ccer(0, reg::TIM_CCER_CC1NE_MSK);
+	}
+}
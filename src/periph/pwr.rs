@@ -0,0 +1,35 @@
+use crate::{regop, reg, thread::sync, periph::{rcc, rtc}};
+use core::arch::asm;
+
+/// Enables writes to the backup domain: RCC_BDCR, and the RTC. It is write-protected after reset
+///
+pub fn unlock_backup_domain() {
+	unsafe {
+		{
+			let _critical = sync::Critical::new();
+			regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB1ENR_OFFSET, reg::RCC_APB1ENR_PWREN_MSK);
+		}
+
+		regop::write_mask(1, reg::PWR_BASE + reg::PWR_CR_OFFSET, reg::PWR_CR_DBP_MSK);
+	}
+}
+
+/// Enters Stop mode: all the clocks but LSI, and LSE are stopped, RAM, and registers are retained. Wakes up on any
+/// EXTI line interrupt, e.g. that of the RTC alarm. The device runs on HSI afterwards, so `config` is re-applied.
+/// The waking interrupt is serviced before this returns. The RTC's shadow registers are resynchronized, so that
+/// `rtc::now` does not return the time Stop mode has been entered at
+///
+pub fn stop(config: rcc::Config) -> rcc::Clocks {
+	unsafe {
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB1ENR_OFFSET, reg::RCC_APB1ENR_PWREN_MSK);
+		regop::write_mask(0, reg::PWR_BASE + reg::PWR_CR_OFFSET, reg::PWR_CR_PDDS_MSK);  // Stop, not Standby
+		regop::write_mask(1, reg::PWR_BASE + reg::PWR_CR_OFFSET, reg::PWR_CR_LPDS_MSK);  // Low-power regulator
+		regop::write_mask(1, reg::SCB_BASE + reg::SCB_SCR_OFFSET, reg::SCB_SCR_SLEEPDEEP_MSK);
+		asm!("wfi");
+		regop::write_mask(0, reg::SCB_BASE + reg::SCB_SCR_OFFSET, reg::SCB_SCR_SLEEPDEEP_MSK);
+	}
+
+	rtc::resync();
+
+	config.freeze()
+}
@@ -1,30 +1,423 @@
-use crate::{wr, rd};
+use crate::{regop, reg, wr, rd};
+use core::ptr;
 
-pub fn get_clock_frequency() -> usize {
-	32_000_000
+pub const HSI_FREQUENCY: usize = 8_000_000;
+const SYSCLK_MAX: usize = 48_000_000;
+const PLL_OUTPUT_MIN: usize = 16_000_000;
+const PLL_INPUT_MIN: usize = 1_000_000;
+const PLL_INPUT_MAX: usize = 24_000_000;
+const HSE_MIN: usize = 4_000_000;  // Crystal, or resonator. Up to `HSE_MAX` in bypass mode as well
+const HSE_MAX: usize = 32_000_000;
+const FLASH_ZERO_WAIT_STATE_MAX: usize = 24_000_000;  // SYSCLK above that requires 1 wait state
+
+/// Frozen clock tree frequencies, Hz. Drivers derive their timing (baudrates, prescalers) from it
+///
+#[derive(Clone, Copy)]
+pub struct Clocks {
+	pub sysclk: usize,
+	pub hclk: usize,  // AHB, core, SysTick (divided by 8)
+	pub pclk: usize,  // APB peripherals
+	pub timclk: usize,  // APB timers. Doubled, unless the APB prescaler is 1
+}
+
+impl Clocks {
+	/// The state after reset: HSI, no prescalers
+	///
+	const RESET: Clocks = Clocks {
+		sysclk: HSI_FREQUENCY,
+		hclk: HSI_FREQUENCY,
+		pclk: HSI_FREQUENCY,
+		timclk: HSI_FREQUENCY,
+	};
+}
+
+static mut CLOCKS: Clocks = Clocks::RESET;
+
+/// Clock tree the system is currently running on
+///
+pub fn clocks() -> Clocks {
+	unsafe {CLOCKS}
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+	Hsi,
+	/// External crystal / ceramic resonator, or, if `bypass` is set, an external clock fed into OSC_IN
+	Hse {frequency: usize, bypass: bool},
+}
+
+/// Clock tree configuration builder. E.g. 48 MHz from HSI: `rcc::Config::hsi().pll(12).freeze()`
+///
+#[derive(Clone, Copy)]
+pub struct Config {
+	source: Source,
+	prediv: usize,  // HSE -> PLL divider. HSI is always divided by 2
+	pll_mul: Option<usize>,
+	hclk_div: usize,
+	pclk_div: usize,
+}
+
+impl Config {
+	pub fn hsi() -> Self {
+		Self::new(Source::Hsi)
+	}
+
+	pub fn hse(frequency: usize, bypass: bool) -> Self {
+		Self::new(Source::Hse {frequency, bypass})
+	}
+
+	fn new(source: Source) -> Self {
+		Config {source, prediv: 1, pll_mul: None, hclk_div: 1, pclk_div: 1}
+	}
+
+	/// Drives SYSCLK from PLL, multiplying its input by `mul`, [2; 16]
+	///
+	pub fn pll(mut self, mul: usize) -> Self {
+		assert!((2..=16).contains(&mul));
+		self.pll_mul = Some(mul);
+		self
+	}
+
+	/// HSE divider at the PLL input, [1; 16]
+	///
+	pub fn prediv(mut self, div: usize) -> Self {
+		assert!((1..=16).contains(&div));
+		self.prediv = div;
+		self
+	}
+
+	/// AHB prescaler: 1, 2, 4, ..., 512, except 32
+	///
+	pub fn hclk_div(mut self, div: usize) -> Self {
+		assert!(div.is_power_of_two() && div <= 512 && div != 32);
+		self.hclk_div = div;
+		self
+	}
+
+	/// APB prescaler: 1, 2, 4, 8, or 16
+	///
+	pub fn pclk_div(mut self, div: usize) -> Self {
+		assert!(div.is_power_of_two() && div <= 16);
+		self.pclk_div = div;
+		self
+	}
+
+	fn source_frequency(&self) -> usize {
+		match self.source {
+			Source::Hsi => HSI_FREQUENCY,
+			Source::Hse {frequency, ..} => frequency,
+		}
+	}
+
+	fn pll_input(&self) -> usize {
+		match self.source {
+			Source::Hsi => HSI_FREQUENCY / 2,
+			Source::Hse {frequency, ..} => frequency / self.prediv,
+		}
+	}
+
+	fn sysclk(&self) -> usize {
+		match self.pll_mul {
+			Some(mul) => self.pll_input() * mul,
+			None => self.source_frequency(),
+		}
+	}
+
+	/// Resulting frequencies. Panics, if the configuration exceeds the part's limits
+	///
+	pub fn clocks(&self) -> Clocks {
+		if let Source::Hse {frequency, bypass} = self.source {
+			assert!(frequency <= HSE_MAX && (bypass || frequency >= HSE_MIN), "HSE frequency is out of range");
+		}
+
+		assert!(self.pll_mul.is_none() || (PLL_INPUT_MIN..=PLL_INPUT_MAX).contains(&self.pll_input()),
+			"PLL input frequency is out of range");
+		let sysclk = self.sysclk();
+		assert!(sysclk <= SYSCLK_MAX);
+		assert!(self.pll_mul.is_none() || sysclk >= PLL_OUTPUT_MIN);
+		let hclk = sysclk / self.hclk_div;
+		let pclk = hclk / self.pclk_div;
+		let timclk = if self.pclk_div == 1 {pclk} else {pclk * 2};
+
+		Clocks {sysclk, hclk, pclk, timclk}
+	}
+
+	/// Applies the configuration. The system is switched to HSI while PLL is being reconfigured, so it is safe to
+	/// call this repeatedly.
+	///
+	/// Never hangs on a faulty clock source: if HSE does not start, or PLL does not lock in time, the closest HSI-based
+	/// configuration is applied instead, and the application is notified (see `set_failure_callback`)
+	///
+	pub fn freeze(self) -> Clocks {
+		match self.apply() {
+			Ok(clocks) => clocks,
+			Err(failure) => fallback(&self, failure),
+		}
+	}
+
+	fn apply(&self) -> Result<Clocks, Failure> {
+		use reg::*;
+		const ENABLE: usize = 0b1;
+		let clocks = self.clocks();
+
+		unsafe {
+			wr!(RCC, CR, HSION, ENABLE);
+
+			// Internal RC, should always start
+			if !wait_ready(|| read(RCC_CR_OFFSET, RCC_CR_HSIRDY_MSK) != 0) || !switch(RCC_CFGR_SW_HSI) {
+				return Err(Failure::Switch);
+			}
+
+			wr!(RCC, CR, CSSON, 0);  // Would trigger on stopping HSE
+			set_flash_latency(SYSCLK_MAX);  // Covers both the current, and the target frequency during the transition
+
+			if let Source::Hse {bypass, ..} = self.source {
+				wr!(RCC, CR, HSEON, 0);
+
+				// HSEBYP may only be written while HSE is off
+				if !wait_ready(|| read(RCC_CR_OFFSET, RCC_CR_HSERDY_MSK) == 0) {
+					return Err(Failure::HseStartup);
+				}
+
+				wr!(RCC, CR, HSEBYP, bypass as usize);
+				wr!(RCC, CR, HSEON, ENABLE);
+
+				if !wait_ready(|| read(RCC_CR_OFFSET, RCC_CR_HSERDY_MSK) != 0) {
+					wr!(RCC, CR, HSEON, 0);
+					return Err(Failure::HseStartup);
+				}
+			}
+
+			let sw = match (self.pll_mul, self.source) {
+				(Some(mul), source) => {
+					// PLL can only be configured while it is off
+					wr!(RCC, CR, PLLON, 0);
+
+					if !wait_ready(|| read(RCC_CR_OFFSET, RCC_CR_PLLRDY_MSK) == 0) {
+						return Err(Failure::PllLock);
+					}
+
+					const PLL_SOURCE_HSI_DIV_2: usize = 0;
+					const PLL_SOURCE_HSE_PREDIV: usize = 1;
+					wr!(RCC, CFGR, PLLSRC, if source == Source::Hsi {PLL_SOURCE_HSI_DIV_2} else {PLL_SOURCE_HSE_PREDIV});
+					wr!(RCC, CFGR2, PREDIV, self.prediv - 1);
+					wr!(RCC, CFGR, PLLMUL, mul - 2);  // 0b0000 is "x2"
+					wr!(RCC, CR, PLLON, ENABLE);
+
+					if !wait_ready(|| read(RCC_CR_OFFSET, RCC_CR_PLLRDY_MSK) != 0) {
+						wr!(RCC, CR, PLLON, 0);
+						return Err(Failure::PllLock);
+					}
+
+					RCC_CFGR_SW_PLL
+				},
+				(None, Source::Hsi) => RCC_CFGR_SW_HSI,
+				(None, Source::Hse {..}) => RCC_CFGR_SW_HSE,
+			};
+
+			// Prescaler codes: 0 - no division, otherwise the MSB is set, and the rest is log2(div) - 1
+			let log2 = |div: usize| div.trailing_zeros() as usize;
+			let hpre = match self.hclk_div {
+				1 => 0,
+				div if div < 32 => 0b1000 | (log2(div) - 1),
+				div => 0b1000 | (log2(div) - 2),  // Division by 32 is skipped
+			};
+			let ppre = match self.pclk_div {
+				1 => 0,
+				div => 0b100 | (log2(div) - 1),
+			};
+			wr!(RCC, CFGR, HPRE, hpre);
+			wr!(RCC, CFGR, PPRE, ppre);
+
+			if !switch(sw) {
+				switch(RCC_CFGR_SW_HSI);
+				return Err(Failure::Switch);
+			}
+
+			set_flash_latency(clocks.sysclk);
+
+			if let Source::Hse {..} = self.source {
+				// Clock Security System. On HSE failure, the hardware switches SYSCLK to HSI, and raises NMI
+				wr!(RCC, CR, CSSON, ENABLE);
+				CONFIG = Some(*self);
+			}
+
+			CLOCKS = clocks;
+		}
+
+		Ok(clocks)
+	}
+}
+
+/// Clock source failures, the system keeps running on HSI after each of them
+///
+#[derive(Clone, Copy, PartialEq)]
+pub enum Failure {
+	HseStartup,  // HSE has not become ready in time
+	HseLoss,  // HSE has stopped, detected by the Clock Security System
+	PllLock,  // PLL has not locked in time
+	Switch,  // SYSCLK has not switched over to the source in time
+}
+
+/// Invoked after the fallback configuration has been applied. Drivers which have derived their timing from the
+/// previous `Clocks` may need reconfiguring. On `Failure::HseLoss`, it is called from the NMI handler, so it must not
+/// block, or use kernel services
+///
+pub type FailureCallback = fn(Failure, &Clocks);
+
+static mut FAILURE_CALLBACK: Option<FailureCallback> = None;
+static mut LAST_FAILURE: Option<Failure> = None;
+static mut CONFIG: Option<Config> = None;  // The last applied HSE-based configuration, to base a fallback on
+
+pub fn set_failure_callback(callback: Option<FailureCallback>) {
+	unsafe {
+		FAILURE_CALLBACK = callback;
+	}
+}
+
+/// The most recent clock failure since reset, if any
+///
+pub fn last_failure() -> Option<Failure> {
+	unsafe {LAST_FAILURE}
+}
+
+/// Volatile read of an RCC register field, not shifted. Keeps `wait_ready` polls from being merged, or hoisted
+///
+fn read(offset: usize, mask: usize) -> usize {
+	unsafe {ptr::read_volatile((reg::RCC_BASE + offset) as *const usize) & mask}
+}
+
+/// Polls a ready flag for a bounded time. SysTick may not be running yet, so the bound is counted in polls. The core
+/// runs on HSI at 8 MHz while the clock tree is reconfigured. A poll takes at least 4 cycles whatever the
+/// optimization: a volatile LDR (2), a test, and a conditional branch, so the bound is at least 100 ms. Startup
+/// times of crystals vary, ~2 ms is typical, the limit is set generously
+///
+fn wait_ready(ready: impl Fn() -> bool) -> bool {
+	const CYCLES_PER_POLL_MIN: usize = 4;
+	const POLLS: usize = HSI_FREQUENCY / 10 / CYCLES_PER_POLL_MIN;  // 100 ms
+
+	(0..POLLS).any(|_| ready())
+}
+
+/// Applies an HSI-based configuration as close to the failed one as possible: HSI / 2 feeds PLL in 4 MHz steps, so
+/// e.g. 48 MHz, and the derived timings are preserved
+///
+fn fallback(failed: &Config, failure: Failure) -> Clocks {
+	let sysclk = failed.sysclk();
+	let mut config = Config::hsi().hclk_div(failed.hclk_div).pclk_div(failed.pclk_div);
+
+	if failure != Failure::PllLock && sysclk > HSI_FREQUENCY {
+		config = config.pll(core::cmp::max(sysclk / (HSI_FREQUENCY / 2), PLL_OUTPUT_MIN / (HSI_FREQUENCY / 2)));
+	}
+
+	let clocks = match config.apply() {
+		Ok(clocks) => clocks,
+		Err(_) => {
+			config.pll_mul = None;
+			config.apply().unwrap_or(Clocks::RESET)  // W/o PLL, and HSE, only a failing HSI is left, nothing to fall back on
+		},
+	};
+
+	unsafe {
+		LAST_FAILURE = Some(failure);
+
+		if let Some(callback) = FAILURE_CALLBACK {
+			callback(failure, &clocks);
+		}
+	}
+
+	clocks
+}
+
+/// NMI is only requested by the Clock Security System on STM32F030
+///
+#[no_mangle]
+pub fn nmi() {
+	use reg::*;
+
+	unsafe {
+		if rd!(RCC, CIR, CSSF) == 0 {
+			crate::error!("Unexpected NMI");
+			return;
+		}
+
+		wr!(RCC, CIR, CSSC, 1);  // Otherwise, NMI is re-entered immediately
+		crate::error!("HSE failure, switching to HSI");
+
+		match CONFIG {
+			Some(config) => {fallback(&config, Failure::HseLoss);},
+			None => {fallback(&Config::hsi(), Failure::HseLoss);},
+		}
+	}
+}
+
+/// Selects SYSCLK source, and waits until the switch takes place. False, if it has not in time
+///
+unsafe fn switch(sw: usize) -> bool {
+	use reg::*;
+	wr!(RCC, CFGR, SW, sw);
+	wait_ready(|| read(RCC_CFGR_OFFSET, RCC_CFGR_SWS_MSK) == sw << RCC_CFGR_SWS_POS)  // SWS values mirror those of SW
+}
+
+/// FLASH access latency has to be increased before raising the frequency, and may be decreased only after lowering it
+///
+unsafe fn set_flash_latency(sysclk: usize) {
+	let acr = reg::FLASH_R_BASE + reg::FLASH_ACR_OFFSET;
+	regop::write_mask(1, acr, reg::FLASH_ACR_PRFTBE_MSK);  // Prefetch buffer hides the wait state on linear code
+	regop::write_mask((sysclk > FLASH_ZERO_WAIT_STATE_MAX) as usize, acr, reg::FLASH_ACR_LATENCY_MSK);
+}
+
+/// Causes of the last reset, RCC_CSR flags. Several may be set at once, e.g. a pin reset accompanies a power-on one
+///
+#[derive(Clone, Copy)]
+pub struct ResetFlags(usize);
+
+impl ResetFlags {
+	pub fn power_on(&self) -> bool {
+		self.0 & reg::RCC_CSR_PORRSTF_MSK != 0
+	}
+
+	pub fn pin(&self) -> bool {
+		self.0 & reg::RCC_CSR_PINRSTF_MSK != 0
+	}
+
+	pub fn software(&self) -> bool {
+		self.0 & reg::RCC_CSR_SFTRSTF_MSK != 0
+	}
+
+	pub fn iwdg(&self) -> bool {
+		self.0 & reg::RCC_CSR_IWDGRSTF_MSK != 0
+	}
+
+	pub fn wwdg(&self) -> bool {
+		self.0 & reg::RCC_CSR_WWDGRSTF_MSK != 0
+	}
+
+	pub fn low_power(&self) -> bool {
+		self.0 & reg::RCC_CSR_LPWRRSTF_MSK != 0
+	}
+}
+
+/// The flags are sticky, they accumulate over resets until `clear_reset_flags`
+///
+pub fn reset_flags() -> ResetFlags {
+	unsafe {
+		ResetFlags(regop::read(reg::RCC_BASE + reg::RCC_CSR_OFFSET))
+	}
+}
+
+pub fn clear_reset_flags() {
+	unsafe {
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_CSR_OFFSET, reg::RCC_CSR_RMVF_MSK);
+	}
 }
 
+/// Enables clocks of the peripherals the application uses
+///
 pub fn configure() {
 	use crate::reg::*;
 	unsafe {
 		const ENABLE: usize = 0b1;
-		// RCC_CR
-		wr!(RCC, CR, HSION, ENABLE);  // Enable HSI
-		wr!(RCC, CR, PLLON, ENABLE);  // Enable PLL, as we want to adjust the clock freq.
-
-		while !(rd!(RCC, CR, PLLRDY) > 0 && rd!(RCC, CR, HSIRDY) > 0) {}  // Busy-wait until the peripherals are on
-
-		// RCC_CFGR
-		const SYSTEM_CLOCK_PLL: usize = 0b10;  // select PLL as system clock
-		wr!(RCC, CFGR, SW, SYSTEM_CLOCK_PLL);
-		const AHB_PRESCALER_NO_DIV: usize = 0;  // AHB (HCLK) prescaler - do not curtail the frequency
-		wr!(RCC, CFGR, HPRE, AHB_PRESCALER_NO_DIV);
-		const APB_PRESCALER_NO_DIV: usize = 0;  // APB (PCLK) prescaler - do not curtail the frequency
-		wr!(RCC, CFGR, PPRE, APB_PRESCALER_NO_DIV);
-		const PLL_SOURCE_HSI_DIV_2: usize = 0;  // Select HSI/2 as input clock source
-		wr!(RCC, CFGR, PLLSRC, PLL_SOURCE_HSI_DIV_2);
-		const PLL_MULT_8: usize = 0b0110;  //  Multiply PLL by 8 (HSI=8(Standard) / 2(HSI/2) * 8(PLLMUL)) = 32. PLL input must not exceed 48MHz
-		wr!(RCC, CFGR, PLLMUL, PLL_MULT_8);
 		// RCC_AHBENR
 		wr!(RCC, AHBENR, GPIOAEN, ENABLE);  // Enable GPIOA port (where USART1 resides)
 		wr!(RCC, AHBENR, GPIOBEN, ENABLE);
@@ -0,0 +1,322 @@
+use crate::{regop, reg, tim, thread::sync, thread::sync::{Lock, LockIsr}, periph::{nvic, irq, pwr}};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+	Lsi,  // ~40 kHz internal RC, inaccurate, and stopped by a power loss
+	Lse {bypass: bool},  // 32.768 kHz crystal, or an external clock if `bypass`
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+	LsiStartup,
+	LseStartup,
+	Init,  // The calendar did not enter initialization mode in time
+	Timeout,  // The RTC did not respond in time, e.g. because RTCCLK has stopped
+}
+
+/// Calendar date, and time, 24-hour format
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DateTime {
+	pub year: u8,  // [0; 99], since 2000
+	pub month: u8,  // [1; 12]
+	pub day: u8,  // [1; 31]
+	pub weekday: u8,  // [1; 7], Monday is 1
+	pub hours: u8,
+	pub minutes: u8,
+	pub seconds: u8,
+}
+
+/// Alarm A match. `None` fields are ignored, e.g. `Alarm {seconds: Some(0), ..Alarm::ANY}` fires every minute
+///
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Alarm {
+	pub day: Option<u8>,  // Day of the month
+	pub hours: Option<u8>,
+	pub minutes: Option<u8>,
+	pub seconds: Option<u8>,
+}
+
+impl Alarm {
+	/// Every second
+	///
+	pub const ANY: Alarm = Alarm {day: None, hours: None, minutes: None, seconds: None};
+}
+
+/// Called from the ISR when the alarm fires
+///
+pub type Callback = fn();
+
+const LSI_STARTUP: tim::Duration = tim::Duration::from_millis(1);
+const LSE_STARTUP: tim::Duration = tim::Duration::from_secs(2);
+const INIT_TIMEOUT: tim::Duration = tim::Duration::from_millis(10);
+const SYNC_TIMEOUT: tim::Duration = tim::Duration::from_millis(10);  // A few RTCCLK cycles normally
+const RTCSEL_LSE: usize = 0b01;
+const RTCSEL_LSI: usize = 0b10;
+const PREDIV_A: usize = 127;  // Max. asynchronous division for the lowest power consumption
+const EXTI_LINE_ALARM: usize = 17;
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+static mut CALLBACK: Option<Callback> = None;
+static mut ALARM: sync::Sem = sync::Sem::new(0, 1);
+static mut WAKEUP_PERIOD: u32 = 0;  // Seconds, 0 - the alarm is not used as a wakeup timer
+
+fn bcd(val: u8) -> usize {
+	((val / 10) << 4 | val % 10) as usize
+}
+
+fn from_bcd(val: usize) -> u8 {
+	((val >> 4 & 0xf) * 10 + (val & 0xf)) as u8
+}
+
+/// Lifts the RTC registers' write protection for the duration of `f`
+///
+fn unprotected<R>(f: impl FnOnce() -> R) -> R {
+	unsafe {
+		regop::write(0xca, reg::RTC_BASE + reg::RTC_WPR_OFFSET);
+		regop::write(0x53, reg::RTC_BASE + reg::RTC_WPR_OFFSET);
+	}
+
+	let ret = f();
+
+	unsafe {
+		regop::write(0xff, reg::RTC_BASE + reg::RTC_WPR_OFFSET);
+	}
+
+	ret
+}
+
+/// Clears `flags` in ISR. They are rc_w0, and INIT is kept
+///
+fn clear_flags(flags: usize) {
+	let isr = reg::RTC_BASE + reg::RTC_ISR_OFFSET;
+
+	unsafe {
+		regop::write(!flags & !reg::RTC_ISR_INIT_MSK | regop::read_mask(isr, reg::RTC_ISR_INIT_MSK) << reg::RTC_ISR_INIT_POS,
+			isr);
+	}
+}
+
+/// Invalidates the calendar shadow registers, so the next read waits for fresh values. Required after a wakeup from
+/// Stop mode, during which the shadows have not been updated, see `pwr::stop`
+///
+pub fn resync() {
+	if unsafe {regop::read_mask(reg::RCC_BASE + reg::RCC_BDCR_OFFSET, reg::RCC_BDCR_RTCEN_MSK)} != 0 {
+		unprotected(|| clear_flags(reg::RTC_ISR_RSF_MSK));
+	}
+}
+
+/// Stops the calendar for `f`, so it can be (re)initialized
+///
+fn init_mode(f: impl FnOnce()) -> Result<(), Error> {
+	let isr = reg::RTC_BASE + reg::RTC_ISR_OFFSET;
+
+	unprotected(|| unsafe {
+		regop::write_mask(1, isr, reg::RTC_ISR_INIT_MSK);
+		let ret = tim::wait_until(|| regop::read_mask(isr, reg::RTC_ISR_INITF_MSK) != 0, INIT_TIMEOUT)
+			.map_err(|_| Error::Init);
+
+		if ret.is_ok() {
+			f();
+			clear_flags(reg::RTC_ISR_RSF_MSK);  // The shadow registers are stale until resynchronized
+		}
+
+		regop::write_mask(0, isr, reg::RTC_ISR_INIT_MSK);
+
+		ret
+	})
+}
+
+/// Selects the RTC clock, and starts the calendar. The backup domain survives resets, so if the RTC is running from
+/// `source` already, the calendar is left intact. Otherwise, the backup domain is reset, as the clock source may
+/// only be changed that way
+///
+pub fn configure(source: Source) -> Result<(), Error> {
+	let bdcr = reg::RCC_BASE + reg::RCC_BDCR_OFFSET;
+	let rtcsel = if let Source::Lsi = source {RTCSEL_LSI} else {RTCSEL_LSE};
+	pwr::unlock_backup_domain();
+
+	unsafe {
+		if source == Source::Lsi {
+			// LSI is not a part of the backup domain, it has to be restarted after each reset
+			regop::write_mask(1, reg::RCC_BASE + reg::RCC_CSR_OFFSET, reg::RCC_CSR_LSION_MSK);
+
+			tim::wait_until(|| regop::read_mask(reg::RCC_BASE + reg::RCC_CSR_OFFSET, reg::RCC_CSR_LSIRDY_MSK) != 0,
+				LSI_STARTUP).map_err(|_| Error::LsiStartup)?;
+		}
+
+		if regop::read_mask(bdcr, reg::RCC_BDCR_RTCEN_MSK) != 0 && regop::read_mask(bdcr, reg::RCC_BDCR_RTCSEL_MSK) == rtcsel {
+			return Ok(());
+		}
+
+		regop::write_mask(1, bdcr, reg::RCC_BDCR_BDRST_MSK);
+		regop::write_mask(0, bdcr, reg::RCC_BDCR_BDRST_MSK);
+
+		if let Source::Lse {bypass} = source {
+			regop::write_mask(bypass as usize, bdcr, reg::RCC_BDCR_LSEBYP_MSK);
+			regop::write_mask(1, bdcr, reg::RCC_BDCR_LSEON_MSK);
+			tim::wait_until(|| regop::read_mask(bdcr, reg::RCC_BDCR_LSERDY_MSK) != 0, LSE_STARTUP)
+				.map_err(|_| Error::LseStartup)?;
+		}
+
+		regop::write_mask(rtcsel, bdcr, reg::RCC_BDCR_RTCSEL_MSK);
+		regop::write_mask(1, bdcr, reg::RCC_BDCR_RTCEN_MSK);
+	}
+
+	// 1 Hz calendar clock: RTCCLK / (PREDIV_A + 1) / (PREDIV_S + 1)
+	let rtcclk = if let Source::Lsi = source {40_000} else {32_768};
+	let prediv_s = rtcclk / (PREDIV_A + 1) - 1;
+
+	init_mode(|| unsafe {
+		regop::write(prediv_s, reg::RTC_BASE + reg::RTC_PRER_OFFSET);  // Both prescalers are written separately
+		regop::write((PREDIV_A << 16) | prediv_s, reg::RTC_BASE + reg::RTC_PRER_OFFSET);
+	})
+}
+
+/// Sets the calendar
+///
+pub fn set(datetime: &DateTime) -> Result<(), Error> {
+	let tr = bcd(datetime.hours) << 16 | bcd(datetime.minutes) << 8 | bcd(datetime.seconds);
+	let dr = bcd(datetime.year) << 16
+		| (datetime.weekday as usize) << reg::RTC_DR_WDU_POS
+		| bcd(datetime.month) << 8
+		| bcd(datetime.day);
+
+	init_mode(|| unsafe {
+		regop::write(tr, reg::RTC_BASE + reg::RTC_TR_OFFSET);
+		regop::write(dr, reg::RTC_BASE + reg::RTC_DR_OFFSET);
+	})
+}
+
+/// Reads the calendar. Reading TR freezes DR's shadow, so the two are consistent
+///
+pub fn now() -> Result<DateTime, Error> {
+	let isr = reg::RTC_BASE + reg::RTC_ISR_OFFSET;
+
+	unsafe {
+		// E.g. after `set`, or `resync`
+		tim::wait_until(|| regop::read_mask(isr, reg::RTC_ISR_RSF_MSK) != 0, SYNC_TIMEOUT).map_err(|_| Error::Timeout)?;
+
+		let tr = regop::read(reg::RTC_BASE + reg::RTC_TR_OFFSET);
+		let dr = regop::read(reg::RTC_BASE + reg::RTC_DR_OFFSET);
+
+		Ok(DateTime {
+			year: from_bcd(dr >> 16),
+			month: from_bcd(dr >> 8 & 0x1f),
+			day: from_bcd(dr & 0x3f),
+			weekday: (dr >> reg::RTC_DR_WDU_POS & 0x7) as u8,
+			hours: from_bcd(tr >> 16 & 0x3f),
+			minutes: from_bcd(tr >> 8 & 0x7f),
+			seconds: from_bcd(tr & 0x7f),
+		})
+	}
+}
+
+fn write_alarm(alarm: &Alarm) -> Result<(), Error> {
+	let field = |val: Option<u8>, mask: usize| val.map_or(mask, bcd);
+	let alrmar = field(alarm.day, reg::RTC_ALRMAR_MSK4_MSK >> 24) << 24
+		| field(alarm.hours, reg::RTC_ALRMAR_MSK3_MSK >> 16) << 16
+		| field(alarm.minutes, reg::RTC_ALRMAR_MSK2_MSK >> 8) << 8
+		| field(alarm.seconds, reg::RTC_ALRMAR_MSK1_MSK);
+	let cr = reg::RTC_BASE + reg::RTC_CR_OFFSET;
+
+	unprotected(|| unsafe {
+		regop::write_mask(0, cr, reg::RTC_CR_ALRAE_MSK);
+		tim::wait_until(|| regop::read_mask(reg::RTC_BASE + reg::RTC_ISR_OFFSET, reg::RTC_ISR_ALRAWF_MSK) != 0,
+			SYNC_TIMEOUT).map_err(|_| Error::Timeout)?;
+		regop::write(alrmar, reg::RTC_BASE + reg::RTC_ALRMAR_OFFSET);
+		regop::write_mask(1, cr, reg::RTC_CR_ALRAIE_MSK);
+		regop::write_mask(1, cr, reg::RTC_CR_ALRAE_MSK);
+
+		Ok(())
+	})
+}
+
+/// Arms alarm A. It is routed through EXTI line 17, so it also wakes the device from Stop mode, see `pwr::stop`
+///
+pub fn set_alarm(alarm: &Alarm, callback: Option<Callback>) -> Result<(), Error> {
+	unsafe {
+		let _critical = sync::Critical::new();
+		CALLBACK = callback;
+		WAKEUP_PERIOD = 0;
+		regop::write_mask(1, reg::EXTI_BASE + reg::EXTI_IMR_OFFSET, 1 << EXTI_LINE_ALARM);
+		regop::write_mask(1, reg::EXTI_BASE + reg::EXTI_RTSR_OFFSET, 1 << EXTI_LINE_ALARM);
+	}
+
+	irq::register(nvic::Interrupt::Rtc, &isr);
+	nvic::set_priority(nvic::Interrupt::Rtc, nvic::PRIORITY_KERNEL);
+	nvic::enable(nvic::Interrupt::Rtc);
+	write_alarm(alarm)
+}
+
+pub fn cancel_alarm() {
+	unsafe {
+		WAKEUP_PERIOD = 0;
+	}
+
+	unprotected(|| unsafe {
+		regop::write_mask(0, reg::RTC_BASE + reg::RTC_CR_OFFSET, reg::RTC_CR_ALRAIE_MSK);
+		regop::write_mask(0, reg::RTC_BASE + reg::RTC_CR_OFFSET, reg::RTC_CR_ALRAE_MSK);
+	});
+}
+
+/// Blocks the calling task until the alarm fires
+///
+pub fn wait_alarm() {
+	unsafe {
+		<sync::Sem as Lock>::lock(&mut ALARM);
+	}
+}
+
+/// Time of day `seconds` after `now`
+///
+fn alarm_after(now: &DateTime, seconds: u32) -> Alarm {
+	let time = (now.hours as u32 * 3600 + now.minutes as u32 * 60 + now.seconds as u32 + seconds) % SECONDS_PER_DAY;
+
+	Alarm {day: None, hours: Some((time / 3600) as u8), minutes: Some((time / 60 % 60) as u8),
+		seconds: Some((time % 60) as u8)}
+}
+
+/// Fires the alarm every `period`, in whole seconds, [1 s; 1 day). This RTC has no dedicated wakeup timer (WUTR is
+/// reserved on STM32F030x4/6/8), so it is emulated w/ alarm A, re-armed from the ISR. It replaces the alarm, and
+/// is stopped by `cancel_alarm`
+///
+pub fn start_wakeup(period: tim::Duration, callback: Option<Callback>) -> Result<(), Error> {
+	let seconds = period.as_secs() as u32;
+	assert!(seconds >= 1 && seconds < SECONDS_PER_DAY, "RTC wakeup period is out of range");
+	set_alarm(&alarm_after(&now()?, seconds), callback)?;
+
+	unsafe {
+		WAKEUP_PERIOD = seconds;
+	}
+
+	Ok(())
+}
+
+fn isr() {
+	unsafe {
+		regop::write(1 << EXTI_LINE_ALARM, reg::EXTI_BASE + reg::EXTI_PR_OFFSET);
+
+		if regop::read_mask(reg::RTC_BASE + reg::RTC_ISR_OFFSET, reg::RTC_ISR_ALRAF_MSK) == 0 {
+			return;
+		}
+
+		clear_flags(reg::RTC_ISR_ALRAF_MSK);
+
+		if WAKEUP_PERIOD != 0 {
+			// Likely the first thing run after a wakeup from Stop mode, before `pwr::stop` returns
+			resync();
+
+			// Both may only time out
+			if now().and_then(|now| write_alarm(&alarm_after(&now, WAKEUP_PERIOD))).is_err() {
+				crate::error!("RTC wakeup could not be re-armed, the RTC has not responded");
+			}
+		}
+
+		<sync::Sem as LockIsr>::unlock(&mut ALARM);
+
+		if let Some(callback) = CALLBACK {
+			callback();
+		}
+	}
+}
@@ -0,0 +1,455 @@
+use crate::{regop, reg, tim, thread::sync, thread::sync::{Lock, LockIsr}, periph::{rcc, nvic, irq, dma,
+	gpio::{self, Pin, Alternate, Output, PushPull, OutputType}}};
+use core::ptr;
+
+/// SPI1 pins. Implemented for `Alternate` pins w/ the matching AF number, see "Alternate functions" tables of the
+/// datasheet
+///
+pub trait SckPin {}
+pub trait MisoPin {}
+pub trait MosiPin {}
+
+/// Stands for the unused data pin of a write-only, or a read-only bus
+///
+pub struct NoPin;
+
+impl MisoPin for NoPin {}
+impl MosiPin for NoPin {}
+
+macro_rules! pin_impl {
+	($trait:ident, $port:ident, $n:expr, $af:expr) => {
+		impl<U: OutputType> $trait for Pin<gpio::$port, $n, Alternate<$af, U>> {}
+	};
+}
+
+pin_impl!(SckPin, A, 5, 0);
+pin_impl!(SckPin, B, 3, 0);
+pin_impl!(MisoPin, A, 6, 0);
+pin_impl!(MisoPin, B, 4, 0);
+pin_impl!(MosiPin, A, 7, 0);
+pin_impl!(MosiPin, B, 5, 0);
+
+/// Clock polarity (CPOL), and phase (CPHA). E.g. `Mode0` - SCK idles low, data is sampled on the rising edge
+///
+#[derive(Clone, Copy)]
+pub enum Mode {
+	Mode0 = 0b00,
+	Mode1 = 0b01,
+	Mode2 = 0b10,
+	Mode3 = 0b11,
+}
+
+#[derive(Clone, Copy)]
+pub enum BitOrder {
+	MsbFirst,
+	LsbFirst,
+}
+
+/// Per-device bus settings, applied at the beginning of each transaction
+///
+#[derive(Clone, Copy)]
+pub struct Config {
+	pub mode: Mode,
+	pub bit_order: BitOrder,
+	pub data_bits: u8,  // [4; 16]. Frames of up to 8 bits are transferred as `u8`, longer ones as `u16`
+	pub frequency_hz: usize,  // Upper bound, the actual SCK frequency is PCLK divided by a power of 2
+}
+
+impl Config {
+	/// Mode 0, MSB first, 8-bit frames
+	///
+	pub const fn new(frequency_hz: usize) -> Self {
+		Config {mode: Mode::Mode0, bit_order: BitOrder::MsbFirst, data_bits: 8, frequency_hz}
+	}
+
+	pub const fn mode(self, mode: Mode) -> Self {
+		Config {mode, ..self}
+	}
+
+	pub const fn bit_order(self, bit_order: BitOrder) -> Self {
+		Config {bit_order, ..self}
+	}
+
+	pub const fn data_bits(self, data_bits: u8) -> Self {
+		Config {data_bits, ..self}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+	Overrun,  // A received frame has not been read in time, and got lost
+	ModeFault,  // NSS has been pulled low by another master
+	Dma,
+	Timeout,  // The peripheral has not shifted a frame in time
+}
+
+/// Data frame type. The data register must be accessed w/ the frame's width, otherwise 8-bit frames are packed
+/// in pairs
+///
+pub trait Word: Copy + Default {
+	const SIZE: dma::Size;
+	const FILL: Self;  // Sent while only reading
+
+	fn from_frame(frame: u16) -> Self;
+	fn into_frame(self) -> u16;
+}
+
+impl Word for u8 {
+	const SIZE: dma::Size = dma::Size::Byte;
+	const FILL: Self = 0xff;
+
+	fn from_frame(frame: u16) -> Self {
+		frame as u8
+	}
+
+	fn into_frame(self) -> u16 {
+		self as u16
+	}
+}
+
+impl Word for u16 {
+	const SIZE: dma::Size = dma::Size::HalfWord;
+	const FILL: Self = 0xffff;
+
+	fn from_frame(frame: u16) -> Self {
+		frame
+	}
+
+	fn into_frame(self) -> u16 {
+		self
+	}
+}
+
+const DR: usize = reg::SPI1_BASE + reg::SPI_DR_OFFSET;
+const DMA_RX_CHANNEL: usize = 2;
+const DMA_TX_CHANNEL: usize = 3;
+const TIMEOUT: tim::Duration = tim::Duration::from_millis(10);  // ~20 16-bit frames at 8 MHz PCLK, and the slowest SCK
+
+/// Interrupt-driven transfer state. The bus lock guarantees there is at most one at a time. Buffers are accessed
+/// through raw pointers, as the frame width is only known at runtime
+///
+struct Transfer {
+	tx: *const u8,  // Null - send `Word::FILL`
+	rx: *mut u8,  // Null - discard the received frames
+	len: usize,
+	sent: usize,
+	received: usize,
+	wide: bool,  // 16-bit frames
+	error: Option<Error>,
+}
+
+static mut BUS: sync::Sem = sync::Sem::new(1, 1);
+static mut DONE: sync::Sem = sync::Sem::new(0, 1);
+static mut TRANSFER: Transfer = Transfer {tx: ptr::null(), rx: ptr::null_mut(), len: 0, sent: 0, received: 0,
+	wide: false, error: None};
+
+/// Enables SPI1's clock. The pins are taken to make sure they have been routed to SPI1, MISO, and MOSI are optional
+/// for write-only, and read-only buses respectively, the unused one is `NoPin`
+///
+pub fn configure<SCK: SckPin, MISO: MisoPin, MOSI: MosiPin>(_sck: SCK, _miso: MISO, _mosi: MOSI) {
+	unsafe {
+		let _critical = sync::Critical::new();
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB2ENR_OFFSET, reg::RCC_APB2ENR_SPI1EN_MSK);
+	}
+
+	irq::register(nvic::Interrupt::Spi1, &isr);
+	nvic::set_priority(nvic::Interrupt::Spi1, nvic::PRIORITY_KERNEL);
+	nvic::enable(nvic::Interrupt::Spi1);
+}
+
+/// A slave on the SPI1 bus, selected by a GPIO pin. Any number of devices may share the bus, each task talking
+/// through its own `Device`, transactions are serialized by the bus lock
+///
+pub struct Device<P: gpio::Port, const N: u8> {
+	cs: Pin<P, N, Output<PushPull>>,
+	config: Config,
+}
+
+impl<P: gpio::Port, const N: u8> Device<P, N> {
+	pub fn new(mut cs: Pin<P, N, Output<PushPull>>, config: Config) -> Self {
+		assert!(config.data_bits >= 4 && config.data_bits <= 16, "SPI data size must be in range [4; 16] bits");
+		cs.set_high();
+
+		Device {cs, config}
+	}
+
+	/// Takes the bus, applies the device's settings, and selects the device for the duration of `f`. Blocks the
+	/// task while another transaction is in progress
+	///
+	pub fn transaction<R>(&mut self, f: impl FnOnce(&mut Bus) -> Result<R, Error>) -> Result<R, Error> {
+		unsafe {
+			<sync::Sem as Lock>::lock(&mut BUS);
+		}
+
+		apply(&self.config);
+		self.cs.set_low();
+		let ret = f(&mut Bus {wide: self.config.data_bits > 8});
+		let idle = wait_idle();
+		self.cs.set_high();
+
+		unsafe {
+			regop::write_mask(0, reg::SPI1_BASE + reg::SPI_CR1_OFFSET, reg::SPI_CR1_SPE_MSK);
+			<sync::Sem as Lock>::unlock(&mut BUS);
+		}
+
+		ret.and_then(|ret| idle.map(|_| ret))
+	}
+}
+
+/// Smallest BR value, such that PCLK / 2^(BR + 1) does not exceed the frequency
+///
+fn prescaler(pclk: usize, frequency_hz: usize) -> usize {
+	(0..8).find(|br| pclk >> (br + 1) <= frequency_hz).unwrap_or(7)
+}
+
+fn apply(config: &Config) {
+	let br = prescaler(rcc::clocks().pclk, config.frequency_hz);
+	// Software NSS management, SSI high keeps the peripheral in master mode, the chip select is driven as a GPIO
+	let cr1 = reg::SPI_CR1_MSTR_MSK
+		| reg::SPI_CR1_SSM_MSK
+		| reg::SPI_CR1_SSI_MSK
+		| (config.mode as usize) << reg::SPI_CR1_CPHA_POS
+		| (br << reg::SPI_CR1_BR_POS)
+		| if let BitOrder::LsbFirst = config.bit_order {reg::SPI_CR1_LSBFIRST_MSK} else {0};
+	// RXNE is raised on a single 8-bit frame, rather than on a half-full FIFO
+	let cr2 = ((config.data_bits as usize - 1) << reg::SPI_CR2_DS_POS)
+		| if config.data_bits <= 8 {reg::SPI_CR2_FRXTH_MSK} else {0};
+
+	unsafe {
+		regop::write(0, reg::SPI1_BASE + reg::SPI_CR1_OFFSET);  // The settings may only be changed while disabled
+		regop::write(cr2, reg::SPI1_BASE + reg::SPI_CR2_OFFSET);
+		regop::write(cr1, reg::SPI1_BASE + reg::SPI_CR1_OFFSET);
+		regop::write_mask(1, reg::SPI1_BASE + reg::SPI_CR1_OFFSET, reg::SPI_CR1_SPE_MSK);
+	}
+}
+
+/// Waits for the last frame to be shifted out, and drains stale data from the RX FIFO
+///
+fn wait_idle() -> Result<(), Error> {
+	let sr = reg::SPI1_BASE + reg::SPI_SR_OFFSET;
+
+	unsafe {
+		tim::wait_until(|| regop::read_mask(sr, reg::SPI_SR_FTLVL_MSK) == 0 && regop::read_mask(sr, reg::SPI_SR_BSY_MSK) == 0,
+			TIMEOUT).map_err(|_| Error::Timeout)?;
+
+		while regop::read_mask(sr, reg::SPI_SR_FRLVL_MSK) != 0 {
+			ptr::read_volatile(DR as *const u8);
+		}
+	}
+
+	Ok(())
+}
+
+/// Reads, and clears the error flags. MODF is cleared by reading SR, and rewriting CR1, OVR - by reading DR, and SR
+///
+fn check_errors() -> Result<(), Error> {
+	unsafe {
+		let sr = regop::read(reg::SPI1_BASE + reg::SPI_SR_OFFSET);
+
+		if sr & reg::SPI_SR_MODF_MSK != 0 {
+			regop::write_mask(1, reg::SPI1_BASE + reg::SPI_CR1_OFFSET, reg::SPI_CR1_MSTR_MSK);
+			Err(Error::ModeFault)
+		} else if sr & reg::SPI_SR_OVR_MSK != 0 {
+			ptr::read_volatile(DR as *const u16);
+			regop::read(reg::SPI1_BASE + reg::SPI_SR_OFFSET);
+			Err(Error::Overrun)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+unsafe fn write_frame(frame: u16, wide: bool) {
+	if wide {
+		ptr::write_volatile(DR as *mut u16, frame);
+	} else {
+		ptr::write_volatile(DR as *mut u8, frame as u8);
+	}
+}
+
+unsafe fn read_frame(wide: bool) -> u16 {
+	if wide {
+		ptr::read_volatile(DR as *const u16)
+	} else {
+		ptr::read_volatile(DR as *const u8) as u16
+	}
+}
+
+/// The bus, while a device is selected. Frames are `u8` for `Config::data_bits` up to 8, and `u16` otherwise.
+///
+/// Each transfer comes in 3 flavors: blocking (polling), interrupt-driven, and DMA. The latter 2 put the task to
+/// sleep until the transfer is complete, and are worth it for longer buffers
+///
+pub struct Bus {
+	wide: bool,
+}
+
+impl Bus {
+	fn check_word<W: Word>(&self) {
+		assert!((W::SIZE == dma::Size::HalfWord) == self.wide, "SPI word type does not match the data size");
+	}
+
+	/// Sends `words`, and replaces them w/ the received ones
+	///
+	pub fn transfer<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+		self.check_word::<W>();
+
+		for word in words.iter_mut() {
+			unsafe {
+				let sr = reg::SPI1_BASE + reg::SPI_SR_OFFSET;
+				tim::wait_until(|| regop::read_mask(sr, reg::SPI_SR_TXE_MSK) != 0, TIMEOUT).map_err(|_| Error::Timeout)?;
+				write_frame(word.into_frame(), self.wide);
+				let mut error = Ok(());
+				tim::wait_until(|| {
+					error = check_errors();
+					error.is_err() || regop::read_mask(sr, reg::SPI_SR_RXNE_MSK) != 0
+				}, TIMEOUT).map_err(|_| Error::Timeout)?;
+				error?;
+
+				*word = W::from_frame(read_frame(self.wide));
+			}
+		}
+
+		check_errors()
+	}
+
+	/// Sends `words`, the received frames are discarded
+	///
+	pub fn write<W: Word>(&mut self, words: &[W]) -> Result<(), Error> {
+		for word in words {
+			self.transfer(&mut [*word])?;
+		}
+
+		Ok(())
+	}
+
+	/// Receives into `words`, sending all-ones frames
+	///
+	pub fn read<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+		words.fill(W::FILL);
+		self.transfer(words)
+	}
+
+	fn start_irq<W: Word>(&mut self, tx: *const u8, rx: *mut u8, len: usize) -> Result<(), Error> {
+		self.check_word::<W>();
+
+		if len == 0 {
+			return Ok(());
+		}
+
+		unsafe {
+			TRANSFER = Transfer {tx, rx, len, sent: 0, received: 0, wide: self.wide, error: None};
+			regop::write_mask(1, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_ERRIE_MSK);
+			regop::write_mask(1, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_RXNEIE_MSK);
+			send_next(&mut TRANSFER);  // The rest is sent from the ISR, a frame per received one
+			<sync::Sem as Lock>::lock(&mut DONE);
+
+			match TRANSFER.error {
+				Some(error) => Err(error),
+				None => Ok(()),
+			}
+		}
+	}
+
+	/// Interrupt-driven `transfer`
+	///
+	pub fn transfer_irq<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+		let buf = words.as_mut_ptr() as *mut u8;
+		self.start_irq::<W>(buf, buf, words.len())
+	}
+
+	/// Interrupt-driven `write`
+	///
+	pub fn write_irq<W: Word>(&mut self, words: &[W]) -> Result<(), Error> {
+		self.start_irq::<W>(words.as_ptr() as *const u8, ptr::null_mut(), words.len())
+	}
+
+	/// Interrupt-driven `read`
+	///
+	pub fn read_irq<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+		self.start_irq::<W>(ptr::null(), words.as_mut_ptr() as *mut u8, words.len())
+	}
+
+	/// DMA-driven `transfer`. Uses DMA channels 2 (RX), and 3 (TX)
+	///
+	pub fn transfer_dma<W: Word>(&mut self, words: &mut [W]) -> Result<(), Error> {
+		self.check_word::<W>();
+
+		if words.is_empty() {
+			return Ok(());
+		}
+
+		let memory = words.as_mut_ptr() as usize;
+		let transfer = |direction| dma::Transfer {peripheral: DR, memory, count: words.len(), size: W::SIZE,
+			direction, memory_increment: true, circular: false};
+
+		// RX is set up first, so no frame is missed. The TX channel reads each word before RX overwrites it
+		unsafe {
+			regop::write_mask(1, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_RXDMAEN_MSK);
+		}
+
+		dma::start(DMA_RX_CHANNEL, &transfer(dma::Direction::PeripheralToMemory));
+		dma::start(DMA_TX_CHANNEL, &transfer(dma::Direction::MemoryToPeripheral));
+
+		unsafe {
+			regop::write_mask(1, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_TXDMAEN_MSK);
+		}
+
+		let ret = dma::wait(DMA_TX_CHANNEL).and(dma::wait(DMA_RX_CHANNEL));
+
+		unsafe {
+			regop::write_mask(0, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_TXDMAEN_MSK);
+			regop::write_mask(0, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_RXDMAEN_MSK);
+		}
+
+		ret.map_err(|_| Error::Dma).and(check_errors())
+	}
+}
+
+unsafe fn send_next(transfer: &mut Transfer) {
+	let frame = if transfer.tx.is_null() {
+		0xffff
+	} else if transfer.wide {
+		(transfer.tx.add(transfer.sent * 2) as *const u16).read_unaligned()
+	} else {
+		*transfer.tx.add(transfer.sent) as u16
+	};
+
+	write_frame(frame, transfer.wide);
+	transfer.sent += 1;
+}
+
+fn isr() {
+	unsafe {
+		let transfer = &mut TRANSFER;
+		let sr = regop::read(reg::SPI1_BASE + reg::SPI_SR_OFFSET);
+
+		if sr & (reg::SPI_SR_MODF_MSK | reg::SPI_SR_OVR_MSK) != 0 {
+			transfer.error = check_errors().err();
+			transfer.received = transfer.len;  // Abort
+		} else if sr & reg::SPI_SR_RXNE_MSK != 0 {
+			let frame = read_frame(transfer.wide);
+
+			if transfer.rx.is_null() {
+				// Discarded
+			} else if transfer.wide {
+				(transfer.rx.add(transfer.received * 2) as *mut u16).write_unaligned(frame);
+			} else {
+				*transfer.rx.add(transfer.received) = frame as u8;
+			}
+
+			transfer.received += 1;
+
+			// A single frame in flight, so the RX FIFO never overflows, and the buffer may be shared by TX, and RX
+			if transfer.sent < transfer.len {
+				send_next(transfer);
+			}
+		}
+
+		if transfer.received == transfer.len {
+			regop::write_mask(0, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_RXNEIE_MSK);
+			regop::write_mask(0, reg::SPI1_BASE + reg::SPI_CR2_OFFSET, reg::SPI_CR2_ERRIE_MSK);
+			<sync::Sem as LockIsr>::unlock(&mut DONE);
+		}
+	}
+}
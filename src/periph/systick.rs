@@ -1,12 +1,79 @@
-use crate::{wr, rd};
+use crate::{wr, periph::{nvic, rcc}, thread::sync};
+use core::ptr;
 
-pub fn configure() {
+/// Kernel tick rate
+///
+pub const TICK_HZ: usize = 1000;
+
+static mut TICKS: u64 = 0;
+static mut CALLBACK: Option<fn()> = None;
+
+pub fn configure(clocks: &rcc::Clocks) {
     use crate::reg::*;
 
     unsafe {
-        wr!(SYSTICK, LOAD, RELOAD, 4_000_000 / 1000 - 1);  // A SysTick request is required every 1ms. The clock line is divided by 8, so 32 / 8 = 4 MHz. Clock frequency is 32MHz.
+        wr!(SYSTICK, LOAD, RELOAD, clocks.hclk / 8 / TICK_HZ - 1);  // A SysTick request is required every 1ms. The counter is clocked from HCLK divided by 8
         wr!(SYSTICK, VAL, CURRENT, 0);  // Initialize current value
         wr!(SYSTICK, CTRL, TICKINT, 1);  // Enable SysTick exception request
         wr!(SYSTICK, CTRL, ENABLE, 1);  // Enable SysTick counter
     }
+
+    nvic::set_system_priority(nvic::SystemHandler::SysTick, nvic::PRIORITY_KERNEL);
+}
+
+/// Number of milliseconds elapsed since `configure()`. Wraps around
+///
+pub fn ticks() -> usize {
+    ticks64() as usize
+}
+
+/// Non-wrapping tick count, see `tim::Instant`
+///
+pub fn ticks64() -> u64 {
+    let _critical = sync::Critical::new();  // 64-bit access is not atomic on Cortex-M0
+
+    unsafe {
+        ptr::read_volatile(ptr::addr_of!(TICKS))
+    }
+}
+
+/// Sets a function to be called from the tick ISR, e.g. for periodic kernel housekeeping
+///
+pub fn set_callback(callback: Option<fn()>) {
+    let _critical = sync::Critical::new();
+
+    unsafe {
+        CALLBACK = callback;
+    }
+}
+
+/// Rate of the SysTick down-counter, HCLK / 8
+///
+pub fn counter_hz() -> usize {
+    rcc::clocks().hclk / 8
+}
+
+/// Returns (current value of the down-counter, the counter's period). The value is in range [0; period). Read
+/// volatilely, so it may be polled in a loop
+///
+pub fn counter() -> (u32, u32) {
+    use crate::reg::*;
+
+    unsafe {
+        let val = ptr::read_volatile((SYSTICK_BASE + SYSTICK_VAL_OFFSET) as *const usize) & SYSTICK_VAL_CURRENT_MSK;
+        let load = ptr::read_volatile((SYSTICK_BASE + SYSTICK_LOAD_OFFSET) as *const usize) & SYSTICK_LOAD_RELOAD_MSK;
+
+        (val as u32, load as u32 + 1)
+    }
+}
+
+#[no_mangle]
+pub fn sys_tick() {
+    unsafe {
+        ptr::write_volatile(ptr::addr_of_mut!(TICKS), TICKS + 1);
+
+        if let Some(callback) = CALLBACK {
+            callback();
+        }
+    }
 }
@@ -0,0 +1,238 @@
+use crate::{regop, reg, tim, thread::sync, periph::{rcc, nvic, irq, gpio::{self, Pin, Alternate, OutputType}}};
+use core::marker::PhantomData;
+
+/// Timer instance, provides the register block's base address, and the clock / interrupt wiring.
+///
+/// All the instances of STM32F030 have 16-bit counters and prescalers
+///
+pub trait Instance: 'static {
+	const BASE: usize;
+	const RCC_ENR: usize;  // Clock enable register offset
+	const RCC_EN_MSK: usize;
+	const IRQ: nvic::Interrupt;  // Update event interrupt
+	const CC_IRQ: nvic::Interrupt;  // Capture / compare interrupt. The same as `IRQ` on all but TIM1
+	const INDEX: usize;  // Index in the driver's state table
+	const ADVANCED: bool;  // Has complementary outputs, and the break / dead-time register (BDTR)
+}
+
+pub struct Tim1;
+pub struct Tim3;
+pub struct Tim14;
+pub struct Tim16;
+pub struct Tim17;
+
+macro_rules! instance_impl {
+	($tim:ident, $base:ident, $enr:ident, $en:ident, $irq:ident, $cc_irq:ident, $index:expr, $advanced:expr) => {
+		impl Instance for $tim {
+			const BASE: usize = reg::$base;
+			const RCC_ENR: usize = reg::$enr;
+			const RCC_EN_MSK: usize = reg::$en;
+			const IRQ: nvic::Interrupt = nvic::Interrupt::$irq;
+			const CC_IRQ: nvic::Interrupt = nvic::Interrupt::$cc_irq;
+			const INDEX: usize = $index;
+			const ADVANCED: bool = $advanced;
+		}
+	};
+}
+
+instance_impl!(Tim1, TIM1_BASE, RCC_APB2ENR_OFFSET, RCC_APB2ENR_TIM1EN_MSK, Tim1BrkUpTrgCom, Tim1Cc, 0, true);
+instance_impl!(Tim3, TIM3_BASE, RCC_APB1ENR_OFFSET, RCC_APB1ENR_TIM3EN_MSK, Tim3, Tim3, 1, false);
+instance_impl!(Tim14, TIM14_BASE, RCC_APB1ENR_OFFSET, RCC_APB1ENR_TIM14EN_MSK, Tim14, Tim14, 2, false);
+instance_impl!(Tim16, TIM16_BASE, RCC_APB2ENR_OFFSET, RCC_APB2ENR_TIM16EN_MSK, Tim16, Tim16, 3, true);
+instance_impl!(Tim17, TIM17_BASE, RCC_APB2ENR_OFFSET, RCC_APB2ENR_TIM17EN_MSK, Tim17, Tim17, 4, true);
+
+/// Pins which a timer's channel #C can be routed to, both as an output (PWM), and an input (capture). Implemented
+/// for `Alternate` pins w/ the matching AF number, so passing a pin configured for another function is a compile error
+///
+pub trait ChannelPin<T: Instance, const C: u8> {}
+
+/// Complementary (inverted, "CHxN") output pins of the advanced timers
+///
+pub trait ComplementaryPin<T: Instance, const C: u8> {}
+
+macro_rules! pin_impl {
+	($trait:ident, $port:ident, $n:expr, $af:expr, $tim:ident, $c:expr) => {
+		impl<U: OutputType> $trait<$tim, $c> for Pin<gpio::$port, $n, Alternate<$af, U>> {}
+	};
+}
+
+// See "Alternate functions" tables of the datasheet
+pin_impl!(ChannelPin, A, 8, 2, Tim1, 1);
+pin_impl!(ChannelPin, A, 9, 2, Tim1, 2);
+pin_impl!(ChannelPin, A, 10, 2, Tim1, 3);
+pin_impl!(ChannelPin, A, 11, 2, Tim1, 4);
+pin_impl!(ComplementaryPin, A, 7, 2, Tim1, 1);
+pin_impl!(ComplementaryPin, B, 0, 2, Tim1, 2);
+pin_impl!(ComplementaryPin, B, 1, 2, Tim1, 3);
+pin_impl!(ChannelPin, A, 6, 1, Tim3, 1);
+pin_impl!(ChannelPin, A, 7, 1, Tim3, 2);
+pin_impl!(ChannelPin, B, 0, 1, Tim3, 3);
+pin_impl!(ChannelPin, B, 1, 1, Tim3, 4);
+pin_impl!(ChannelPin, B, 4, 1, Tim3, 1);
+pin_impl!(ChannelPin, B, 5, 1, Tim3, 2);
+pin_impl!(ChannelPin, A, 4, 4, Tim14, 1);
+pin_impl!(ChannelPin, A, 7, 4, Tim14, 1);
+pin_impl!(ChannelPin, B, 1, 0, Tim14, 1);
+pin_impl!(ChannelPin, A, 6, 5, Tim16, 1);
+pin_impl!(ChannelPin, B, 8, 2, Tim16, 1);
+pin_impl!(ComplementaryPin, B, 6, 2, Tim16, 1);
+pin_impl!(ChannelPin, A, 7, 5, Tim17, 1);
+pin_impl!(ChannelPin, B, 9, 2, Tim17, 1);
+pin_impl!(ComplementaryPin, B, 7, 2, Tim17, 1);
+
+pub const NINSTANCES: usize = 5;
+const COUNTER_MAX: usize = 0xffff;
+
+/// Called from the ISR on each update event (counter overflow)
+///
+pub type Callback = fn();
+
+#[derive(Clone, Copy)]
+struct State {
+	callback: Option<Callback>,
+	one_shot: bool,
+}
+
+static mut STATES: [State; NINSTANCES] = [State {callback: None, one_shot: false}; NINSTANCES];
+
+/// Timer driver. The counter is incremented at `tick_hz` rate which is derived from the `rcc` timer clock
+///
+pub struct Timer<T: Instance> {
+	tick_hz: usize,
+	_t: PhantomData<T>,
+}
+
+impl<T: Instance> Timer<T> {
+	/// Enables the timer's clock, and sets its prescaler, so the counter runs at `tick_hz`. The counter is stopped
+	///
+	pub fn new(clocks: &rcc::Clocks, tick_hz: usize) -> Self {
+		let psc = clocks.timclk / tick_hz;
+		assert!(psc >= 1 && psc - 1 <= COUNTER_MAX, "Timer tick frequency is out of the prescaler's range");
+
+		unsafe {
+			let _critical = sync::Critical::new();
+			regop::write_mask(1, reg::RCC_BASE + T::RCC_ENR, T::RCC_EN_MSK);
+		}
+
+		let timer = Timer {tick_hz: clocks.timclk / psc, _t: PhantomData};
+		timer.stop();
+
+		unsafe {
+			regop::write(psc - 1, T::BASE + reg::TIM_PSC_OFFSET);
+			regop::write_mask(1, T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_ARPE_MSK);  // Preload ARR, it is updated on UEV
+			regop::write_mask(1, T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_URS_MSK);  // Only overflows raise UIF, not UG
+			regop::write(0, T::BASE + reg::TIM_SR_OFFSET);
+		}
+
+		timer
+	}
+
+	/// Actual counter rate. May differ from the requested one due to the prescaler's integer division
+	///
+	pub fn tick_hz(&self) -> usize {
+		self.tick_hz
+	}
+
+	fn ticks(&self, duration: tim::Duration) -> usize {
+		duration.to_ticks(self.tick_hz).map_or(usize::MAX, |t| t as usize)  // Out of range values are caught by `start`
+	}
+
+	/// Loads the period, resets the counter, and starts counting
+	///
+	fn start(&mut self, period_ticks: usize, one_shot: bool) {
+		assert!(period_ticks >= 1 && period_ticks - 1 <= COUNTER_MAX, "Timer period is out of the counter's range");
+
+		unsafe {
+			STATES[T::INDEX].one_shot = one_shot;
+			regop::write(period_ticks - 1, T::BASE + reg::TIM_ARR_OFFSET);
+			regop::write(reg::TIM_EGR_UG_MSK, T::BASE + reg::TIM_EGR_OFFSET);  // Transfer PSC, ARR into the shadow registers, and reset the counter
+			regop::write_mask(1, T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_CEN_MSK);
+		}
+	}
+
+	/// Invokes the callback every `period`
+	///
+	pub fn start_periodic(&mut self, period: tim::Duration) {
+		let ticks = self.ticks(period);
+		self.start(ticks, false);
+	}
+
+	/// Invokes the callback once, after `timeout`. The counter is stopped afterwards
+	///
+	pub fn start_one_shot(&mut self, timeout: tim::Duration) {
+		let ticks = self.ticks(timeout);
+		self.start(ticks, true);
+	}
+
+	/// Counts through the full 16-bit range, and wraps. Intended for time measurement w/ `counter()`
+	///
+	pub fn start_free_running(&mut self) {
+		self.start(COUNTER_MAX + 1, false);
+	}
+
+	pub fn stop(&self) {
+		unsafe {
+			regop::write_mask(0, T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_CEN_MSK);
+		}
+	}
+
+	pub fn is_running(&self) -> bool {
+		unsafe {
+			regop::read_mask(T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_CEN_MSK) != 0
+		}
+	}
+
+	pub fn counter(&self) -> u16 {
+		unsafe {
+			regop::read(T::BASE + reg::TIM_CNT_OFFSET) as u16
+		}
+	}
+
+	/// Sets a function to be called from the ISR on each update event. `None` disables the interrupt
+	///
+	pub fn set_callback(&mut self, callback: Option<Callback>) {
+		{
+			let _critical = sync::Critical::new();
+
+			unsafe {
+				STATES[T::INDEX].callback = callback;
+			}
+		}
+
+		match callback {
+			Some(_) => {
+				irq::register(T::IRQ, &isr::<T>);
+
+				unsafe {
+					regop::write_mask(1, T::BASE + reg::TIM_DIER_OFFSET, reg::TIM_DIER_UIE_MSK);
+				}
+
+				nvic::set_priority(T::IRQ, nvic::PRIORITY_KERNEL);
+				nvic::enable(T::IRQ);
+			},
+			None => unsafe {
+				regop::write_mask(0, T::BASE + reg::TIM_DIER_OFFSET, reg::TIM_DIER_UIE_MSK);
+			},
+		}
+	}
+}
+
+fn isr<T: Instance>() {
+	unsafe {
+		if regop::read_mask(T::BASE + reg::TIM_SR_OFFSET, reg::TIM_SR_UIF_MSK) == 0 {
+			return;  // TIM1 shares the vector w/ break, trigger, and commutation events
+		}
+
+		regop::write(!reg::TIM_SR_UIF_MSK, T::BASE + reg::TIM_SR_OFFSET);  // rc_w0, other flags are left intact
+
+		let state = STATES[T::INDEX];
+
+		if state.one_shot {
+			regop::write_mask(0, T::BASE + reg::TIM_CR1_OFFSET, reg::TIM_CR1_CEN_MSK);
+		}
+
+		if let Some(callback) = state.callback {
+			callback();
+		}
+	}
+}
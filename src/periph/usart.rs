@@ -1,12 +1,12 @@
-use crate::{reg, regop, wr, rd};
+use crate::{reg, regop, wr, rd, periph::rcc};
 
-pub fn configure() {
+/// USART1 is clocked from PCLK, see `rcc::configure`
+///
+pub fn configure(clocks: &rcc::Clocks) {
 	use reg::*;
-	// The baudrate is calculated based on the assumption that the system clock's frequency is 32 MHz.
-	const SYSTEM_CLOCK_FREQ: usize = 32_000_000;
 	const BAUDRATE: usize = 57_600;
 	unsafe {
-		wr!(USART, 1, BRR, SYSTEM_CLOCK_FREQ / BAUDRATE);  // Set baudrate
+		wr!(USART, 1, BRR, (clocks.pclk + BAUDRATE / 2) / BAUDRATE);  // Set baudrate, 16x oversampling
 		wr!(USART, 1, CR1, RE, 1);  // Usart, enable receiver
 		wr!(USART, 1, CR1, TE, 1);  // Usart, enable transmitter
 		wr!(USART, 1, CR1, UE, 1);  // Usart, enable
@@ -17,12 +17,23 @@ pub fn read(buf: &mut [u8]) {
 	use reg::*;
 	unsafe {
 		for c in buf {
-			while !(rd!(USART, "1", ISR, RXNE) != 1) {}  // Wait for the read-ready bit
+			wr!(USART, "1", ICR, ORECF, 1);  // Clear overrun, if there was one, so it does not stall the reception
+			while rd!(USART, "1", ISR, RXNE) != 1 {}  // Wait for the read-ready bit
 			*c = rd!(USART, "1", RDR) as u8;
 		}
 	}
 }
 
+/// Received byte, if there is one. Does not block
+///
+pub fn try_read() -> Option<u8> {
+	use reg::*;
+	unsafe {
+		wr!(USART, "1", ICR, ORECF, 1);  // Clear overrun, if there was one, so it does not stall the reception
+		(rd!(USART, "1", ISR, RXNE) == 1).then(|| rd!(USART, "1", RDR) as u8)
+	}
+}
+
 pub fn write(buf: &[u8]) {
 	use reg::*;
 	unsafe {
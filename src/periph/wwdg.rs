@@ -0,0 +1,92 @@
+use crate::{regop, reg, tim, thread::sync, periph::{rcc, nvic, irq}};
+
+/// Called from the early wakeup interrupt, a counter tick before the reset, to log state, or save a crash record.
+/// Depending on the clock, it has from ~85 us to ~680 us to do so. Kicking the watchdog from it averts the reset
+///
+pub type EarlyWakeup = fn();
+
+const T_MIN: usize = 0x40;  // The reset is triggered as the counter goes below
+const T_MAX: usize = 0x7f;
+const PRESCALER: usize = 4096;  // Fixed PCLK divider, WDGTB divides further by 2^WDGTB
+
+static mut COUNTER: usize = T_MAX;  // Reload value
+static mut EARLY_WAKEUP: Option<EarlyWakeup> = None;
+
+/// Starts the window watchdog. It resets the device unless `kick` is called within `timeout` after the previous
+/// kick, but not earlier than `window` after it. `Duration::ZERO` window allows kicking anytime. The timeout is up
+/// to 64 counter ticks of 4096 * 8 PCLK cycles, i.e. ~43 ms at 48 MHz. Once started, it cannot be stopped but by a
+/// reset. It is paused while the core is halted by a debugger
+///
+pub fn start(clocks: &rcc::Clocks, timeout: tim::Duration, window: tim::Duration) {
+	let tick_hz = |wdgtb: usize| clocks.pclk / (PRESCALER << wdgtb);
+	let wdgtb = (0..4).find(|tb| timeout.to_ticks(tick_hz(*tb)).map_or(false, |t| t as usize <= T_MAX - T_MIN + 1))
+		.expect("WWDG timeout is out of range");
+	let ticks = timeout.to_ticks(tick_hz(wdgtb)).unwrap() as usize;
+	assert!(ticks >= 1, "WWDG timeout is shorter than a counter tick");
+	let counter = T_MIN - 1 + ticks;
+	let window_ticks = window.to_ticks_ceil(tick_hz(wdgtb)).map_or(usize::MAX, |t| t as usize);
+	assert!(window_ticks < ticks, "WWDG window must be shorter than the timeout");
+
+	unsafe {
+		let _critical = sync::Critical::new();
+		regop::write_mask(1, reg::RCC_BASE + reg::RCC_APB1ENR_OFFSET, reg::RCC_APB1ENR_WWDGEN_MSK);
+		regop::write_mask(1, reg::DBGMCU_BASE + reg::DBGMCU_APB1FZ_OFFSET, reg::DBGMCU_APB1_FZ_DBG_WWDG_STOP_MSK);
+	}
+
+	unsafe {
+		COUNTER = counter;
+		regop::write_mask(wdgtb, reg::WWDG_BASE + reg::WWDG_CFR_OFFSET, reg::WWDG_CFR_WDGTB_MSK);
+		// The counter may only be reloaded while it is not above W
+		regop::write_mask(counter - window_ticks, reg::WWDG_BASE + reg::WWDG_CFR_OFFSET, reg::WWDG_CFR_W_MSK);
+		regop::write(reg::WWDG_CR_WDGA_MSK | counter, reg::WWDG_BASE + reg::WWDG_CR_OFFSET);
+	}
+}
+
+/// Reloads the counter. Kicking too early, i.e. within the window, resets the device
+///
+pub fn kick() {
+	unsafe {
+		regop::write(reg::WWDG_CR_WDGA_MSK | COUNTER, reg::WWDG_BASE + reg::WWDG_CR_OFFSET);
+	}
+}
+
+/// Enables the early wakeup interrupt. It cannot be disabled but by a reset, passing `None` only drops the callback,
+/// and the interrupt is logged instead
+///
+pub fn set_early_wakeup(callback: Option<EarlyWakeup>) {
+	{
+		let _critical = sync::Critical::new();
+
+		unsafe {
+			EARLY_WAKEUP = callback;
+		}
+	}
+
+	irq::register(nvic::Interrupt::Wwdg, &isr);
+	// The last chance to act, so preempts everything else
+	nvic::set_priority(nvic::Interrupt::Wwdg, nvic::PRIORITY_HIGHEST);
+
+	unsafe {
+		regop::write(0, reg::WWDG_BASE + reg::WWDG_SR_OFFSET);
+		regop::write_mask(1, reg::WWDG_BASE + reg::WWDG_CFR_OFFSET, reg::WWDG_CFR_EWI_MSK);
+	}
+
+	nvic::enable(nvic::Interrupt::Wwdg);
+}
+
+/// Whether the last reset has been caused by the watchdog, see `rcc::reset_flags`
+///
+pub fn caused_reset() -> bool {
+	rcc::reset_flags().wwdg()
+}
+
+fn isr() {
+	unsafe {
+		regop::write(0, reg::WWDG_BASE + reg::WWDG_SR_OFFSET);  // rc_w0
+
+		match EARLY_WAKEUP {
+			Some(callback) => callback(),
+			None => crate::error!("WWDG is about to reset the device"),
+		}
+	}
+}
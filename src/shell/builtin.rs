@@ -0,0 +1,71 @@
+use super::{commands, regmap, Args, Error};
+use crate::{mem, reg, shell_command, thread::task};
+use core::fmt::Write;
+use core::ptr;
+
+shell_command!("help", "List commands", |_, out| {
+	for command in commands() {
+		let _ = write!(out, "{:<8}{}\r\n", command.name, command.help);
+	}
+	Ok(())
+});
+
+shell_command!("tasks", "List task slots", |_, out| {
+	for info in task::tasks() {
+		let _ = match info.state {
+			task::TaskState::Free => write!(out, "{} free\r\n", info.id),
+			task::TaskState::Running => write!(out, "{} running\r\n", info.id),
+			task::TaskState::Pending => write!(out, "{} pending sp={:#x} pc={:#x}\r\n", info.id, info.sp, info.pc),
+		};
+	}
+	Ok(())
+});
+
+shell_command!("mem", "Show heap usage", |_, out| {
+	let usage = mem::usage();
+	let _ = write!(out, "heap: total {} used {} free {}\r\n", usage.total, usage.used, usage.free);
+	Ok(())
+});
+
+/// Parses either a register name (see `regmap`), or an address
+///
+fn address(args: &mut Args) -> Result<usize, Error> {
+	let arg = args.next_str()?;
+
+	match regmap::lookup(arg) {
+		Some(addr) => Ok(addr),
+		None => Args(arg.split_ascii_whitespace()).next_usize().map_err(|_| Error::NotFound),
+	}
+}
+
+shell_command!("peek", "peek <PERIPH.REG | addr>", |args, out| {
+	let addr = address(args)?;
+	let val = match addr % 4 {
+		0 => unsafe {ptr::read_volatile(addr as *const u32) as usize},
+		2 => unsafe {ptr::read_volatile(addr as *const u16) as usize},  // Option bytes are half-word wide
+		_ => return Err(Error::InvalidArgument),
+	};
+	let _ = write!(out, "{:#010x}: {:#010x}\r\n", addr, val);
+	Ok(())
+});
+
+shell_command!("poke", "poke <PERIPH.REG | addr> <value>", |args, _| {
+	let addr = address(args)?;
+	let val = args.next_usize()?;
+
+	match addr % 4 {
+		0 => unsafe {ptr::write_volatile(addr as *mut u32, val as u32)},
+		2 => unsafe {ptr::write_volatile(addr as *mut u16, val as u16)},
+		_ => return Err(Error::InvalidArgument),
+	}
+	Ok(())
+});
+
+shell_command!("reset", "Reset the MCU", |_, _| {
+	use reg::*;
+	const VECTKEY: usize = 0x05fa;
+	unsafe {
+		wr!(SCB, AIRCR, (VECTKEY << SCB_AIRCR_VECTKEY_POS) | SCB_AIRCR_SYSRESETREQ_MSK);
+	}
+	loop {}
+});
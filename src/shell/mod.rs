@@ -0,0 +1,184 @@
+mod builtin;
+mod regmap;
+
+use crate::{log, tim, periph::usart, thread::task};
+use core::fmt;
+use core::fmt::Write;
+
+const LINE_SIZE: usize = 64;
+const PROMPT: &str = "> ";
+const POLL_PERIOD: tim::Duration = tim::Duration::from_millis(1);
+
+/// Command handler error
+///
+pub enum Error {
+	MissingArgument,
+	InvalidArgument,
+	NotFound,  // Unknown command, register, etc.
+}
+
+impl Error {
+	fn as_str(&self) -> &'static str {
+		match self {
+			Error::MissingArgument => "missing argument",
+			Error::InvalidArgument => "invalid argument",
+			Error::NotFound => "not found",
+		}
+	}
+}
+
+/// Whitespace-separated command arguments
+///
+pub struct Args<'a>(core::str::SplitAsciiWhitespace<'a>);
+
+impl<'a> Args<'a> {
+	pub fn next_str(&mut self) -> Result<&'a str, Error> {
+		self.0.next().ok_or(Error::MissingArgument)
+	}
+
+	/// Parses a decimal, or a "0x"-prefixed hexadecimal number
+	///
+	pub fn next_usize(&mut self) -> Result<usize, Error> {
+		let arg = self.next_str()?;
+		let parsed = match arg.strip_prefix("0x").or(arg.strip_prefix("0X")) {
+			Some(hex) => usize::from_str_radix(hex, 16),
+			None => arg.parse::<usize>(),
+		};
+
+		parsed.map_err(|_| Error::InvalidArgument)
+	}
+}
+
+/// Writes into USART1. Output shares the line w/ the UART logger, so it is written under `log::write_locked`, a
+/// command's whole response at once
+///
+pub struct Console;
+
+impl fmt::Write for Console {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		usart::write(s.as_bytes());
+		Ok(())
+	}
+}
+
+pub type Handler = fn(&mut Args, &mut Console) -> Result<(), Error>;
+
+/// Command descriptor. Instances are collected by the linker into `.shell_commands`, see `shell_command!`
+///
+pub struct Command {
+	pub name: &'static str,
+	pub help: &'static str,
+	pub run: Handler,
+}
+
+/// Registers a command, e.g. `shell_command!("hello", "Greets the user", |_, out| {...});`
+///
+#[macro_export]
+macro_rules! shell_command {
+	($name:expr, $help:expr, $run:expr) => {
+		const _: () = {
+			#[used]
+			#[link_section = ".shell_commands"]
+			static COMMAND: $crate::shell::Command = $crate::shell::Command {
+				name: $name,
+				help: $help,
+				run: $run,
+			};
+		};
+	};
+}
+
+/// Commands registered through `shell_command!`
+///
+fn commands() -> &'static [Command] {
+	extern "C" {
+		static _sshell_commands: u8;
+		static _eshell_commands: u8;
+	}
+
+	unsafe {
+		let begin = &_sshell_commands as *const u8 as *const Command;
+		let end = &_eshell_commands as *const u8 as *const Command;
+		core::slice::from_raw_parts(begin, end.offset_from(begin) as usize)
+	}
+}
+
+fn execute(line: &str, console: &mut Console) {
+	let mut args = Args(line.split_ascii_whitespace());
+	let name = match args.next_str() {
+		Ok(name) => name,
+		Err(_) => return,  // Empty line
+	};
+	let result = match commands().iter().find(|c| c.name == name) {
+		Some(command) => (command.run)(&mut args, console),
+		None => Err(Error::NotFound),
+	};
+
+	if let Err(e) = result {
+		let _ = write!(console, "{}: {}\r\n", name, e.as_str());
+	}
+}
+
+/// Waits for a byte, sleeping between polls, so the shell task idles instead of spinning on RXNE. A byte takes
+/// ~0.17 ms at 57600 baud, so pasted input may lose characters, typed input does not
+///
+fn read_byte() -> u8 {
+	loop {
+		if let Some(c) = usart::try_read() {
+			return c;
+		}
+
+		task::sleep(POLL_PERIOD);
+	}
+}
+
+/// Shell task runner. Reads lines from USART1 w/ basic editing (backspace, Ctrl+U), and executes them
+///
+pub fn run() {
+	const BACKSPACE: u8 = 0x08;
+	const DELETE: u8 = 0x7f;
+	const CTRL_U: u8 = 0x15;
+	const ERASE: &str = "\x08 \x08";
+
+	let mut console = Console{};
+	let mut line = [0u8; LINE_SIZE];
+	let mut len = 0;
+	let mut last = 0u8;
+	log::write_locked(|| {let _ = console.write_str(PROMPT);});
+
+	loop {
+		let c = read_byte();
+
+		match c {
+			b'\n' if last == b'\r' => {},  // "\r\n" line ending, the line has already been handled
+			b'\r' | b'\n' => {
+				log::write_locked(|| {
+					let _ = console.write_str("\r\n");
+					execute(core::str::from_utf8(&line[..len]).unwrap_or(""), &mut console);
+					let _ = console.write_str(PROMPT);
+				});
+				len = 0;
+			},
+			BACKSPACE | DELETE if len > 0 => {
+				len -= 1;
+				log::write_locked(|| {let _ = console.write_str(ERASE);});
+			},
+			CTRL_U => {
+				log::write_locked(|| {
+					for _ in 0..len {
+						let _ = console.write_str(ERASE);
+					}
+				});
+				len = 0;
+			},
+			0x20..=0x7e if len < LINE_SIZE => {  // Printable ASCII
+				line[len] = c;
+				len += 1;
+				log::write_locked(|| usart::write(&[c]));  // Echo
+			},
+			_ => {},
+		}
+
+		last = c;
+	}
+}
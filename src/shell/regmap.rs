@@ -0,0 +1,230 @@
+use crate::reg::*;
+
+/// Register name table for `peek` and `poke`. Follows the naming of `reg.rs`, so `GPIOA.MODER` stands for
+/// `GPIOA_BASE + GPIO_MODER_OFFSET`
+///
+/// (peripheral, base address, register group)
+const PERIPHERALS: &[(&str, usize, &str)] = &[
+	("ADC1", ADC1_BASE, "ADC"),
+	("ADC", ADC_BASE, "ADC_COMMON"),
+	("CRC", CRC_BASE, "CRC"),
+	("DBGMCU", DBGMCU_BASE, "DBGMCU"),
+	("DMA1", DMA1_BASE, "DMA"),
+	("DMA1_CHANNEL1", DMA1_CHANNEL1_BASE, "DMA_CHANNEL"),
+	("DMA1_CHANNEL2", DMA1_CHANNEL2_BASE, "DMA_CHANNEL"),
+	("DMA1_CHANNEL3", DMA1_CHANNEL3_BASE, "DMA_CHANNEL"),
+	("DMA1_CHANNEL4", DMA1_CHANNEL4_BASE, "DMA_CHANNEL"),
+	("DMA1_CHANNEL5", DMA1_CHANNEL5_BASE, "DMA_CHANNEL"),
+	("EXTI", EXTI_BASE, "EXTI"),
+	("FLASH", FLASH_R_BASE, "FLASH"),
+	("OB", OB_BASE, "OB"),
+	("GPIOA", GPIOA_BASE, "GPIO"),
+	("GPIOB", GPIOB_BASE, "GPIO"),
+	("GPIOC", GPIOC_BASE, "GPIO"),
+	("GPIOD", GPIOD_BASE, "GPIO"),
+	("GPIOF", GPIOF_BASE, "GPIO"),
+	("SYSCFG", SYSCFG_BASE, "SYSCFG"),
+	("I2C1", I2C1_BASE, "I2C"),
+	("IWDG", IWDG_BASE, "IWDG"),
+	("PWR", PWR_BASE, "PWR"),
+	("RCC", RCC_BASE, "RCC"),
+	("RTC", RTC_BASE, "RTC"),
+	("SPI1", SPI1_BASE, "SPI"),
+	("TIM1", TIM1_BASE, "TIM"),
+	("TIM3", TIM3_BASE, "TIM"),
+	("TIM14", TIM14_BASE, "TIM"),
+	("TIM16", TIM16_BASE, "TIM"),
+	("TIM17", TIM17_BASE, "TIM"),
+	("USART1", USART1_BASE, "USART"),
+	("WWDG", WWDG_BASE, "WWDG"),
+	("NVIC", NVIC_BASE, "NVIC"),
+	("SCB", SCB_BASE, "SCB"),
+	("SYSTICK", SYSTICK_BASE, "SYSTICK"),
+];
+
+/// (register group, register, offset)
+const REGISTERS: &[(&str, &str, usize)] = &[
+	("ADC", "ISR", ADC_ISR_OFFSET),
+	("ADC", "IER", ADC_IER_OFFSET),
+	("ADC", "CR", ADC_CR_OFFSET),
+	("ADC", "CFGR1", ADC_CFGR1_OFFSET),
+	("ADC", "CFGR2", ADC_CFGR2_OFFSET),
+	("ADC", "SMPR", ADC_SMPR_OFFSET),
+	("ADC", "TR", ADC_TR_OFFSET),
+	("ADC", "CHSELR", ADC_CHSELR_OFFSET),
+	("ADC", "DR", ADC_DR_OFFSET),
+	("ADC_COMMON", "CCR", ADC_COMMON_CCR_OFFSET),
+	("CRC", "DR", CRC_DR_OFFSET),
+	("CRC", "IDR", CRC_IDR_OFFSET),
+	("CRC", "CR", CRC_CR_OFFSET),
+	("CRC", "INIT", CRC_INIT_OFFSET),
+	("DBGMCU", "IDCODE", DBGMCU_IDCODE_OFFSET),
+	("DBGMCU", "CR", DBGMCU_CR_OFFSET),
+	("DBGMCU", "APB1FZ", DBGMCU_APB1FZ_OFFSET),
+	("DBGMCU", "APB2FZ", DBGMCU_APB2FZ_OFFSET),
+	("DMA_CHANNEL", "CCR", DMA_CHANNEL_CCR_OFFSET),
+	("DMA_CHANNEL", "CNDTR", DMA_CHANNEL_CNDTR_OFFSET),
+	("DMA_CHANNEL", "CPAR", DMA_CHANNEL_CPAR_OFFSET),
+	("DMA_CHANNEL", "CMAR", DMA_CHANNEL_CMAR_OFFSET),
+	("DMA", "ISR", DMA_ISR_OFFSET),
+	("DMA", "IFCR", DMA_IFCR_OFFSET),
+	("EXTI", "IMR", EXTI_IMR_OFFSET),
+	("EXTI", "EMR", EXTI_EMR_OFFSET),
+	("EXTI", "RTSR", EXTI_RTSR_OFFSET),
+	("EXTI", "FTSR", EXTI_FTSR_OFFSET),
+	("EXTI", "SWIER", EXTI_SWIER_OFFSET),
+	("EXTI", "PR", EXTI_PR_OFFSET),
+	("FLASH", "ACR", FLASH_ACR_OFFSET),
+	("FLASH", "KEYR", FLASH_KEYR_OFFSET),
+	("FLASH", "OPTKEYR", FLASH_OPTKEYR_OFFSET),
+	("FLASH", "SR", FLASH_SR_OFFSET),
+	("FLASH", "CR", FLASH_CR_OFFSET),
+	("FLASH", "AR", FLASH_AR_OFFSET),
+	("FLASH", "OBR", FLASH_OBR_OFFSET),
+	("FLASH", "WRPR", FLASH_WRPR_OFFSET),
+	("OB", "RDP", OB_RDP_OFFSET),
+	("OB", "USER", OB_USER_OFFSET),
+	("OB", "DATA0", OB_DATA0_OFFSET),
+	("OB", "DATA1", OB_DATA1_OFFSET),
+	("OB", "WRP0", OB_WRP0_OFFSET),
+	("GPIO", "MODER", GPIO_MODER_OFFSET),
+	("GPIO", "OTYPER", GPIO_OTYPER_OFFSET),
+	("GPIO", "OSPEEDR", GPIO_OSPEEDR_OFFSET),
+	("GPIO", "PUPDR", GPIO_PUPDR_OFFSET),
+	("GPIO", "IDR", GPIO_IDR_OFFSET),
+	("GPIO", "ODR", GPIO_ODR_OFFSET),
+	("GPIO", "BSRR", GPIO_BSRR_OFFSET),
+	("GPIO", "LCKR", GPIO_LCKR_OFFSET),
+	("GPIO", "AFR_0", GPIO_AFR_0_OFFSET),
+	("GPIO", "AFR_1", GPIO_AFR_1_OFFSET),
+	("GPIO", "BRR", GPIO_BRR_OFFSET),
+	("SYSCFG", "CFGR1", SYSCFG_CFGR1_OFFSET),
+	("SYSCFG", "EXTICR_0", SYSCFG_EXTICR_0_OFFSET),
+	("SYSCFG", "EXTICR_1", SYSCFG_EXTICR_1_OFFSET),
+	("SYSCFG", "EXTICR_2", SYSCFG_EXTICR_2_OFFSET),
+	("SYSCFG", "EXTICR_3", SYSCFG_EXTICR_3_OFFSET),
+	("SYSCFG", "CFGR2", SYSCFG_CFGR2_OFFSET),
+	("I2C", "CR1", I2C_CR1_OFFSET),
+	("I2C", "CR2", I2C_CR2_OFFSET),
+	("I2C", "OAR1", I2C_OAR1_OFFSET),
+	("I2C", "OAR2", I2C_OAR2_OFFSET),
+	("I2C", "TIMINGR", I2C_TIMINGR_OFFSET),
+	("I2C", "TIMEOUTR", I2C_TIMEOUTR_OFFSET),
+	("I2C", "ISR", I2C_ISR_OFFSET),
+	("I2C", "ICR", I2C_ICR_OFFSET),
+	("I2C", "PECR", I2C_PECR_OFFSET),
+	("I2C", "RXDR", I2C_RXDR_OFFSET),
+	("I2C", "TXDR", I2C_TXDR_OFFSET),
+	("IWDG", "KR", IWDG_KR_OFFSET),
+	("IWDG", "PR", IWDG_PR_OFFSET),
+	("IWDG", "RLR", IWDG_RLR_OFFSET),
+	("IWDG", "SR", IWDG_SR_OFFSET),
+	("IWDG", "WINR", IWDG_WINR_OFFSET),
+	("PWR", "CR", PWR_CR_OFFSET),
+	("PWR", "CSR", PWR_CSR_OFFSET),
+	("RCC", "CR", RCC_CR_OFFSET),
+	("RCC", "CFGR", RCC_CFGR_OFFSET),
+	("RCC", "CIR", RCC_CIR_OFFSET),
+	("RCC", "APB2RSTR", RCC_APB2RSTR_OFFSET),
+	("RCC", "APB1RSTR", RCC_APB1RSTR_OFFSET),
+	("RCC", "AHBENR", RCC_AHBENR_OFFSET),
+	("RCC", "APB2ENR", RCC_APB2ENR_OFFSET),
+	("RCC", "APB1ENR", RCC_APB1ENR_OFFSET),
+	("RCC", "BDCR", RCC_BDCR_OFFSET),
+	("RCC", "CSR", RCC_CSR_OFFSET),
+	("RCC", "AHBRSTR", RCC_AHBRSTR_OFFSET),
+	("RCC", "CFGR2", RCC_CFGR2_OFFSET),
+	("RCC", "CFGR3", RCC_CFGR3_OFFSET),
+	("RCC", "CR2", RCC_CR2_OFFSET),
+	("RTC", "TR", RTC_TR_OFFSET),
+	("RTC", "DR", RTC_DR_OFFSET),
+	("RTC", "CR", RTC_CR_OFFSET),
+	("RTC", "ISR", RTC_ISR_OFFSET),
+	("RTC", "PRER", RTC_PRER_OFFSET),
+	("RTC", "ALRMAR", RTC_ALRMAR_OFFSET),
+	("RTC", "WPR", RTC_WPR_OFFSET),
+	("RTC", "SSR", RTC_SSR_OFFSET),
+	("RTC", "SHIFTR", RTC_SHIFTR_OFFSET),
+	("RTC", "TSTR", RTC_TSTR_OFFSET),
+	("RTC", "TSDR", RTC_TSDR_OFFSET),
+	("RTC", "TSSSR", RTC_TSSSR_OFFSET),
+	("RTC", "CALR", RTC_CALR_OFFSET),
+	("RTC", "TAFCR", RTC_TAFCR_OFFSET),
+	("RTC", "ALRMASSR", RTC_ALRMASSR_OFFSET),
+	("SPI", "CR1", SPI_CR1_OFFSET),
+	("SPI", "CR2", SPI_CR2_OFFSET),
+	("SPI", "SR", SPI_SR_OFFSET),
+	("SPI", "DR", SPI_DR_OFFSET),
+	("SPI", "CRCPR", SPI_CRCPR_OFFSET),
+	("SPI", "RXCRCR", SPI_RXCRCR_OFFSET),
+	("SPI", "TXCRCR", SPI_TXCRCR_OFFSET),
+	("SPI", "I2SCFGR", SPI_I2SCFGR_OFFSET),
+	("TIM", "CR1", TIM_CR1_OFFSET),
+	("TIM", "CR2", TIM_CR2_OFFSET),
+	("TIM", "SMCR", TIM_SMCR_OFFSET),
+	("TIM", "DIER", TIM_DIER_OFFSET),
+	("TIM", "SR", TIM_SR_OFFSET),
+	("TIM", "EGR", TIM_EGR_OFFSET),
+	("TIM", "CCMR1", TIM_CCMR1_OFFSET),
+	("TIM", "CCMR2", TIM_CCMR2_OFFSET),
+	("TIM", "CCER", TIM_CCER_OFFSET),
+	("TIM", "CNT", TIM_CNT_OFFSET),
+	("TIM", "PSC", TIM_PSC_OFFSET),
+	("TIM", "ARR", TIM_ARR_OFFSET),
+	("TIM", "RCR", TIM_RCR_OFFSET),
+	("TIM", "CCR1", TIM_CCR1_OFFSET),
+	("TIM", "CCR2", TIM_CCR2_OFFSET),
+	("TIM", "CCR3", TIM_CCR3_OFFSET),
+	("TIM", "CCR4", TIM_CCR4_OFFSET),
+	("TIM", "BDTR", TIM_BDTR_OFFSET),
+	("TIM", "DCR", TIM_DCR_OFFSET),
+	("TIM", "DMAR", TIM_DMAR_OFFSET),
+	("TIM", "OR", TIM_OR_OFFSET),
+	("USART", "CR1", USART_CR1_OFFSET),
+	("USART", "CR2", USART_CR2_OFFSET),
+	("USART", "CR3", USART_CR3_OFFSET),
+	("USART", "BRR", USART_BRR_OFFSET),
+	("USART", "GTPR", USART_GTPR_OFFSET),
+	("USART", "RTOR", USART_RTOR_OFFSET),
+	("USART", "RQR", USART_RQR_OFFSET),
+	("USART", "ISR", USART_ISR_OFFSET),
+	("USART", "ICR", USART_ICR_OFFSET),
+	("USART", "RDR", USART_RDR_OFFSET),
+	("USART", "TDR", USART_TDR_OFFSET),
+	("WWDG", "CR", WWDG_CR_OFFSET),
+	("WWDG", "CFR", WWDG_CFR_OFFSET),
+	("WWDG", "SR", WWDG_SR_OFFSET),
+	("NVIC", "ISER_0", NVIC_ISER_0_OFFSET),
+	("NVIC", "ICER_0", NVIC_ICER_0_OFFSET),
+	("NVIC", "ISPR_0", NVIC_ISPR_0_OFFSET),
+	("NVIC", "ICPR_0", NVIC_ICPR_0_OFFSET),
+	("NVIC", "IP_0", NVIC_IP_0_OFFSET),
+	("NVIC", "IP_1", NVIC_IP_1_OFFSET),
+	("NVIC", "IP_2", NVIC_IP_2_OFFSET),
+	("NVIC", "IP_3", NVIC_IP_3_OFFSET),
+	("NVIC", "IP_4", NVIC_IP_4_OFFSET),
+	("NVIC", "IP_5", NVIC_IP_5_OFFSET),
+	("NVIC", "IP_6", NVIC_IP_6_OFFSET),
+	("NVIC", "IP_7", NVIC_IP_7_OFFSET),
+	("SCB", "CPUID", SCB_CPUID_OFFSET),
+	("SCB", "ICSR", SCB_ICSR_OFFSET),
+	("SCB", "AIRCR", SCB_AIRCR_OFFSET),
+	("SCB", "SCR", SCB_SCR_OFFSET),
+	("SCB", "CCR", SCB_CCR_OFFSET),
+	("SCB", "SHP_0", SCB_SHP_0_OFFSET),
+	("SCB", "SHP_1", SCB_SHP_1_OFFSET),
+	("SCB", "SHCSR", SCB_SHCSR_OFFSET),
+	("SYSTICK", "CTRL", SYSTICK_CTRL_OFFSET),
+	("SYSTICK", "LOAD", SYSTICK_LOAD_OFFSET),
+	("SYSTICK", "VAL", SYSTICK_VAL_OFFSET),
+	("SYSTICK", "CALIB", SYSTICK_CALIB_OFFSET),
+];
+/// Resolves `PERIPHERAL.REGISTER` (case-insensitive) into an address
+///
+pub fn lookup(name: &str) -> Option<usize> {
+	let (peripheral, register) = name.split_once('.')?;
+	let (_, base, group) = PERIPHERALS.iter().find(|(p, _, _)| p.eq_ignore_ascii_case(peripheral))?;
+	let (_, _, offset) = REGISTERS.iter().find(|(g, r, _)| g == group && r.eq_ignore_ascii_case(register))?;
+
+	Some(base + offset)
+}
@@ -1,2 +1,3 @@
 #[macro_use] pub mod sync;
 pub mod task;
+pub mod supervisor;
@@ -0,0 +1,147 @@
+use crate::{tim, thread::{sync, task::{self, TaskId}}, periph::{iwdg, rcc, systick}};
+
+const NSLOTS: usize = 4;
+const MAGIC: u32 = 0x5355_5056;  // "SUPV"
+
+#[derive(Clone, Copy)]
+struct Slot {
+	task: TaskId,
+	deadline: u64,  // Ticks
+	last: u64,  // Tick of the last check-in
+}
+
+/// What has been found starving before the reset
+///
+#[derive(Clone, Copy, Debug)]
+pub struct Starved {
+	pub task: TaskId,
+	pub deadline: tim::Duration,
+}
+
+#[repr(C)]
+struct Record {
+	magic: u32,
+	task: TaskId,
+	deadline_us: u64,
+}
+
+#[derive(Debug)]
+pub enum Error {
+	NoSlots,  // `NSLOTS` tasks are being watched already
+	NotATask,  // Called outside of a task
+	Watchdog,  // The watchdog has not taken `timeout` in time, see `iwdg::start`
+}
+
+static mut SLOTS: [Option<Slot>; NSLOTS] = [None; NSLOTS];
+static mut STARVING: bool = false;
+static mut LAST: Option<Starved> = None;  // Taken over from `RECORD` by `start`
+
+// Not initialized on reset. All the fields are integers, so any bit pattern left over is valid
+#[link_section = ".noinit"]
+static mut RECORD: Record = Record {magic: 0, task: 0, deadline_us: 0};
+
+/// Starts task liveness supervision on top of the independent watchdog. Each watched task has to `check_in` within
+/// its own deadline. The watchdog is kicked from the tick ISR only while every watched task is alive, so a hung one
+/// resets the device. The starved task is recorded in memory that survives the reset, see `starved`.
+///
+/// `timeout` is the watchdog's. It bounds the time from a missed deadline to the reset, and does not have to cover
+/// the deadlines.
+///
+/// The record of the previous run is consumed, and the reset flags are cleared, so that a later reset of another
+/// cause does not report the same task again. On `Error::Watchdog`, the watchdog runs unsupervised, and is never
+/// kicked
+///
+pub fn start(timeout: tim::Duration) -> Result<(), Error> {
+	unsafe {
+		LAST = take_record();
+	}
+
+	rcc::clear_reset_flags();
+	iwdg::start(timeout).map_err(|_| Error::Watchdog)?;
+	systick::set_callback(Some(poll));
+
+	Ok(())
+}
+
+/// Task that has starved, if it has caused the last reset. Available once `start` has been called
+///
+pub fn starved() -> Option<Starved> {
+	unsafe {LAST}
+}
+
+fn take_record() -> Option<Starved> {
+	let record = unsafe {&mut RECORD};
+	let valid = record.magic == MAGIC && iwdg::caused_reset();
+	record.magic = 0;
+
+	valid.then(|| Starved {task: record.task, deadline: tim::Duration::from_micros(record.deadline_us)})
+}
+
+/// Registration of the calling task. The supervision of it ends when dropped
+///
+pub struct Watch {
+	slot: usize,
+}
+
+/// Starts watching the calling task, which has to check in at least once per `deadline`
+///
+pub fn watch(deadline: tim::Duration) -> Result<Watch, Error> {
+	let task = task::current().ok_or(Error::NotATask)?;
+	let deadline = deadline.to_ticks_ceil(systick::TICK_HZ).map_or(u64::MAX, |t| t as u64);
+	let _critical = sync::Critical::new();
+
+	unsafe {
+		let slot = SLOTS.iter().position(Option::is_none).ok_or(Error::NoSlots)?;
+		SLOTS[slot] = Some(Slot {task, deadline, last: systick::ticks64()});
+
+		Ok(Watch {slot})
+	}
+}
+
+impl Watch {
+	pub fn check_in(&self) {
+		let now = systick::ticks64();
+		let _critical = sync::Critical::new();
+
+		unsafe {
+			if let Some(ref mut slot) = SLOTS[self.slot] {
+				slot.last = now;
+			}
+		}
+	}
+}
+
+impl Drop for Watch {
+	fn drop(&mut self) {
+		let _critical = sync::Critical::new();
+
+		unsafe {
+			SLOTS[self.slot] = None;
+		}
+	}
+}
+
+/// Called each tick. Once a task has starved, the watchdog is never kicked again
+///
+fn poll() {
+	unsafe {
+		if STARVING {
+			return;
+		}
+
+		let now = systick::ticks64();
+
+		for slot in SLOTS.iter().flatten() {
+			if now.saturating_sub(slot.last) > slot.deadline {
+				let deadline = tim::Duration::from_ticks(slot.deadline, systick::TICK_HZ);
+				RECORD = Record {magic: MAGIC, task: slot.task, deadline_us: deadline.as_micros()};
+				STARVING = true;
+				crate::error!("Task {} has not checked in for {} ms, resetting", slot.task, deadline.as_millis());
+
+				return;
+			}
+		}
+
+		iwdg::kick();
+	}
+}
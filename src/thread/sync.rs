@@ -1,4 +1,4 @@
-use core::{arch::asm, intrinsics};
+use core::arch::asm;
 
 extern "C" {
 	pub fn critical_enter();
@@ -20,6 +20,12 @@ macro_rules! critical {
 	}
 }
 
+/// Checks whether the code is being run from an exception handler (ISR, fault handler), as opposed to a task
+///
+pub fn in_isr() -> bool {
+	crate::periph::irq::active_exception() != 0
+}
+
 /// RAII wrapper over critical section invoke
 pub struct Critical {}
 
@@ -59,10 +65,8 @@ pub struct Sem {
 }
 
 impl Sem {
-	pub fn new(free: u8, max: u8) -> Sem {
-		if free > max {
-			intrinsics::abort();
-		}
+	pub const fn new(free: u8, max: u8) -> Sem {
+		assert!(free <= max);
 		Self {free, max}
 	}
 }
@@ -72,7 +76,7 @@ impl Lock for Sem {
 		let mut ret: bool = false;
 		let _critical = Critical::new();
 
-		if !self.free > 0 {
+		if self.free > 0 {
 			self.free -= 1;
 			ret = true;
 		}
@@ -112,7 +116,7 @@ impl LockIsr for Sem {
 	fn try_lock(&mut self) -> bool {
 		let mut ret = false;
 
-		if self.free < self.max {
+		if self.free > 0 {
 			self.free -= 1;
 			ret = true;
 		}
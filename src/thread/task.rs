@@ -1,5 +1,4 @@
-use crate::{mem, thread::sync, log, log::Logger};
-use core::fmt::Write;
+use crate::{mem, thread::sync, log, tim, periph::systick};
 use core::alloc::GlobalAlloc;
 use core::ops::{Index, IndexMut, Drop};
 use core::arch::asm;
@@ -7,7 +6,7 @@ use core::convert::{From};
 use core::marker::{PhantomData, PhantomPinned};
 
 pub type Runner = fn() -> ();
-type TaskId = usize;
+pub type TaskId = usize;
 const TASK_ID_INVALID: TaskId = 0xffffffff;
 
 /// Stores offsets of certains registers in `StackFrame`
@@ -167,6 +166,64 @@ impl<const N: usize> ContextQueue<N> {
 
 static mut CONTEXT_QUEUE: ContextQueue<2> = ContextQueue::<2>::new();
 
+/// Id. of the task that is currently being run, if there is one
+///
+pub fn current() -> Option<TaskId> {
+	match unsafe {CONTEXT_QUEUE.current} {
+		TASK_ID_INVALID => None,
+		id => Some(id),
+	}
+}
+
+#[derive(Clone, Copy)]
+pub enum TaskState {
+	Free,  // The slot is not occupied
+	Pending,
+	Running,
+}
+
+/// Describes a slot of `CONTEXT_QUEUE`. Saved SP and PC are only meaningful for pending tasks
+///
+#[derive(Clone, Copy)]
+pub struct TaskInfo {
+	pub id: TaskId,
+	pub state: TaskState,
+	pub sp: usize,
+	pub pc: usize,
+}
+
+/// Iterates over a snapshot of `CONTEXT_QUEUE`
+///
+pub fn tasks() -> impl Iterator<Item = TaskInfo> {
+	let (context_queue, current) = {
+		let _critical = sync::Critical::new();
+		unsafe {(CONTEXT_QUEUE.context_queue, CONTEXT_QUEUE.current)}
+	};
+
+	context_queue.into_iter().enumerate().map(move |(id, context)| match context {
+		Context::Uninitialized => TaskInfo {id, state: TaskState::Free, sp: 0, pc: 0},
+		Context::Initialized(stack_frame) => TaskInfo {
+			id,
+			state: if id == current {TaskState::Running} else {TaskState::Pending},
+			sp: stack_frame[StackFrameLayout::Sp],
+			pc: stack_frame[StackFrameLayout::Pc],
+		},
+	})
+}
+
+/// Blocks the calling task for at least `duration`. The core idles till the next interrupt between checks
+///
+pub fn sleep(duration: tim::Duration) {
+	// `Instant::now()` is truncated to a tick, so one is added
+	let deadline = tim::deadline(duration + tim::Duration::from_ticks(1, systick::TICK_HZ));
+
+	while !deadline.is_reached() {
+		unsafe {
+			asm!("wfe");
+		}
+	}
+}
+
 pub struct Stack<'a>(&'a mut usize, usize);  // Begin of memory chunk, length (multiple of type)
 
 impl Stack<'_> {
@@ -1,5 +1,284 @@
-pub enum Duration {
-    Microseconds(usize),
-    Milliseconds(usize),
-    Seconds(usize),
+use crate::periph::systick;
+use core::ops::{Add, AddAssign, Sub, SubAssign, Mul, Div};
+
+/// Time span w/ microsecond resolution. 64-bit, so it does not overflow in practice, and conversions into a
+/// peripheral's ticks are checked
+///
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub struct Duration {
+    us: u64,
+}
+
+impl Duration {
+    pub const ZERO: Duration = Duration {us: 0};
+    pub const MAX: Duration = Duration {us: u64::MAX};
+
+    pub const fn from_micros(us: u64) -> Self {
+        Duration {us}
+    }
+
+    pub const fn from_millis(ms: u64) -> Self {
+        Duration {us: ms.saturating_mul(1_000)}
+    }
+
+    pub const fn from_secs(s: u64) -> Self {
+        Duration {us: s.saturating_mul(1_000_000)}
+    }
+
+    /// Duration of `ticks` periods of a `hz` clock, rounded down to a microsecond
+    ///
+    pub const fn from_ticks(ticks: u64, hz: usize) -> Self {
+        Duration {us: ticks.saturating_mul(1_000_000) / hz as u64}
+    }
+
+    pub const fn as_micros(&self) -> u64 {
+        self.us
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.us / 1_000
+    }
+
+    pub const fn as_secs(&self) -> u64 {
+        self.us / 1_000_000
+    }
+
+    /// Number of `hz` clock periods, rounded down. `None`, if it does not fit into `u32`
+    ///
+    pub fn to_ticks(&self, hz: usize) -> Option<u32> {
+        u32::try_from(self.us.checked_mul(hz as u64)? / 1_000_000).ok()
+    }
+
+    /// Number of `hz` clock periods, rounded up, so waiting for it is never shorter than the duration
+    ///
+    pub fn to_ticks_ceil(&self, hz: usize) -> Option<u32> {
+        u32::try_from(self.us.checked_mul(hz as u64)?.div_ceil(1_000_000)).ok()
+    }
+
+    pub fn checked_add(self, other: Duration) -> Option<Duration> {
+        self.us.checked_add(other.us).map(Duration::from_micros)
+    }
+
+    pub fn checked_sub(self, other: Duration) -> Option<Duration> {
+        self.us.checked_sub(other.us).map(Duration::from_micros)
+    }
+
+    pub fn saturating_sub(self, other: Duration) -> Duration {
+        Duration::from_micros(self.us.saturating_sub(other.us))
+    }
+}
+
+/// Panics on overflow, see `checked_add`
+///
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        self.checked_add(other).expect("Duration overflow")
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+/// Panics on underflow, see `checked_sub`, and `saturating_sub`
+///
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        self.checked_sub(other).expect("Duration underflow")
+    }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<u32> for Duration {
+    type Output = Duration;
+
+    fn mul(self, n: u32) -> Duration {
+        Duration::from_micros(self.us.checked_mul(n as u64).expect("Duration overflow"))
+    }
+}
+
+impl Div<u32> for Duration {
+    type Output = Duration;
+
+    fn div(self, n: u32) -> Duration {
+        Duration::from_micros(self.us / n as u64)
+    }
+}
+
+/// Point in time, measured by the kernel tick (SysTick) since its start. Monotonic, the tick counter is 64-bit, so
+/// it does not wrap around. Resolution is 1 / `systick::TICK_HZ`
+///
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Instant {
+    ticks: u64,
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Instant {ticks: systick::ticks64()}
+    }
+
+    /// Time passed since `earlier`, zero if `earlier` is in the future
+    ///
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_ticks(self.ticks.saturating_sub(earlier.ticks), systick::TICK_HZ)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    /// Whether the instant, e.g. a deadline produced by `deadline()`, is now or in the past
+    ///
+    pub fn is_reached(&self) -> bool {
+        Instant::now() >= *self
+    }
+
+    /// Time left till the instant, zero if it has been reached
+    ///
+    pub fn remaining(&self) -> Duration {
+        self.duration_since(Instant::now())
+    }
+
+    /// Rounds the duration up to whole ticks. `now()` is truncated to a tick, so a deadline may still be reached up
+    /// to 1 tick early
+    ///
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        let ticks = duration.to_ticks_ceil(systick::TICK_HZ)?;
+        self.ticks.checked_add(ticks as u64).map(|ticks| Instant {ticks})
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        self.checked_add(duration).expect("Instant overflow")
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// Instant `timeout` from now. E.g. `let deadline = tim::deadline(d); while !deadline.is_reached() {...}`
+///
+pub fn deadline(timeout: Duration) -> Instant {
+    Instant::now() + timeout
+}
+
+/// Counts SysTick counter cycles passed since its creation. Unlike `Instant`, it is accurate to a fraction of a
+/// microsecond, and keeps working in ISRs, and critical sections.
+///
+/// Counter wraps are taken from the tick count, so a task may be preempted for any number of ticks between polls.
+/// Where the tick interrupt cannot run (ISRs, critical sections), they are detected from the counter value instead,
+/// so it must be polled at least once per tick there
+///
+struct Stopwatch {
+    last_ticks: u64,
+    last: u32,
+    unticked: u64,  // Wraps detected from the counter, but not yet reflected by the tick count
+    elapsed: u64,
+}
+
+impl Stopwatch {
+    /// Consistent (tick count, counter value) pair
+    ///
+    fn sample() -> (u64, u32, u32) {
+        loop {
+            let ticks = systick::ticks64();
+            let (val, period) = systick::counter();
+
+            if systick::ticks64() == ticks {
+                return (ticks, val, period);
+            }
+        }
+    }
+
+    fn start() -> Self {
+        let (ticks, val, _) = Self::sample();
+        Stopwatch {last_ticks: ticks, last: val, unticked: 0, elapsed: 0}
+    }
+
+    fn poll(&mut self) -> u64 {
+        let (ticks, now, period) = Self::sample();
+        let ticked = ticks - self.last_ticks;
+
+        let wraps = if ticked > self.unticked {
+            let wraps = ticked - self.unticked;
+            self.unticked = 0;
+            wraps
+        } else {
+            // The tick is pending, or cannot be served. The counter counts down, and reloads after reaching 0
+            self.unticked -= ticked;
+            let wrapped = now > self.last;
+            self.unticked += wrapped as u64;
+            wrapped as u64
+        };
+
+        // Non-negative, as the counter is below `period`, and a wrap has occurred if it has grown
+        self.elapsed += wraps * period as u64 + self.last as u64 - now as u64;
+        self.last_ticks = ticks;
+        self.last = now;
+
+        self.elapsed
+    }
+}
+
+/// The condition has not been met in time
+///
+#[derive(Debug)]
+pub struct Timeout;
+
+/// Busy-waits for `duration`. Calibrated against the `rcc` clock through SysTick, so `systick::configure` must have
+/// been called. Resolution is 8 core clock cycles. Tasks should prefer `task::sleep` for longer waits
+///
+pub fn delay(duration: Duration) {
+    let cycles = duration.to_ticks_ceil(systick::counter_hz()).map_or(u64::MAX, |c| c as u64);
+    let mut stopwatch = Stopwatch::start();
+
+    while stopwatch.poll() < cycles {}
+}
+
+pub fn delay_us(us: u32) {
+    delay(Duration::from_micros(us as u64));
+}
+
+pub fn delay_ms(ms: u32) {
+    delay(Duration::from_millis(ms as u64));
+}
+
+/// Polls `condition` until it is met, or `timeout` passes. Intended for waiting on register flags, e.g.
+/// `tim::wait_until(|| unsafe {rd!(RCC, CR, PLLRDY)} != 0, Duration::from_millis(2))?`. The calling task may be
+/// preempted. In ISRs, and critical sections, a single check must take less than a kernel tick
+///
+pub fn wait_until(mut condition: impl FnMut() -> bool, timeout: Duration) -> Result<(), Timeout> {
+    let cycles = timeout.to_ticks_ceil(systick::counter_hz()).map_or(u64::MAX, |c| c as u64);
+    let mut stopwatch = Stopwatch::start();
+
+    loop {
+        if condition() {
+            return Ok(());
+        }
+
+        if stopwatch.poll() >= cycles {
+            // The condition might have been met while the time was being checked
+            return if condition() {Ok(())} else {Err(Timeout)};
+        }
+    }
 }
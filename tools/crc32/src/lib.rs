@@ -0,0 +1,119 @@
+#![no_std]
+
+#[path = "../../../src/periph/crc/soft.rs"]
+mod soft;
+
+pub use soft::*;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Model of the STM32F030 CRC unit as `periph::crc` configures it: MSB-first shift register w/ polynomial
+	/// 0x04C11DB7, and initial value 0xFFFFFFFF, REV_IN reversing the bits of each input unit, and REV_OUT - of the
+	/// result
+	///
+	struct Unit {
+		crc: u32,
+	}
+
+	impl Unit {
+		fn new() -> Self {
+			Unit {crc: 0xffff_ffff}
+		}
+
+		fn feed(&mut self, data: u32, bits: u32) {
+			self.crc ^= data << (32 - bits);
+
+			for _ in 0..bits {
+				self.crc = if self.crc & 0x8000_0000 != 0 {(self.crc << 1) ^ 0x04c1_1db7} else {self.crc << 1};
+			}
+		}
+
+		fn write_byte(&mut self, b: u8) {
+			self.feed(b.reverse_bits() as u32, 8);  // REV_IN = 0b01
+		}
+
+		fn write_word(&mut self, w: u32) {
+			self.feed(w.reverse_bits(), 32);  // REV_IN = 0b11
+		}
+
+		fn value(&self) -> u32 {
+			self.crc.reverse_bits() ^ 0xffff_ffff  // REV_OUT, and `crc::FINAL_XOR`
+		}
+	}
+
+	fn pattern(len: usize) -> impl Iterator<Item = u32> {
+		(0..len as u32).map(|i| i.wrapping_mul(0x9e37_79b9) ^ (i << 7))
+	}
+
+	#[test]
+	fn check_vectors() {
+		assert_eq!(checksum(b""), 0);
+		assert_eq!(checksum(b"123456789"), 0xcbf4_3926);
+		assert_eq!(checksum(b"The quick brown fox jumps over the lazy dog"), 0x414f_a339);
+	}
+
+	#[test]
+	fn incremental() {
+		let data = b"The quick brown fox jumps over the lazy dog";
+
+		for split in 0..data.len() {
+			let mut crc = Crc::new();
+			crc.update(&data[..split]).update(&data[split..]);
+			assert_eq!(crc.value(), checksum(data));
+		}
+	}
+
+	#[test]
+	fn words_are_little_endian_bytes() {
+		let words: [u32; 16] = core::array::from_fn(|i| pattern(16).nth(i).unwrap());
+		let mut bytes = [0u8; 64];
+
+		for (chunk, w) in bytes.chunks_exact_mut(4).zip(words.iter()) {
+			chunk.copy_from_slice(&w.to_le_bytes());
+		}
+
+		assert_eq!(checksum_words(&[0x3433_3231, 0x3837_3635]), checksum(b"12345678"));
+		assert_eq!(checksum_words(&words), checksum(&bytes));
+	}
+
+	#[test]
+	fn matches_unit_byte_mode() {
+		let mut unit = Unit::new();
+		b"123456789".iter().for_each(|b| unit.write_byte(*b));
+		assert_eq!(unit.value(), 0xcbf4_3926);
+
+		let mut unit = Unit::new();
+		let mut crc = Crc::new();
+
+		for b in pattern(100).map(|w| w as u8) {
+			unit.write_byte(b);
+			crc.update(&[b]);
+			assert_eq!(unit.value(), crc.value());
+		}
+	}
+
+	#[test]
+	fn matches_unit_word_mode() {
+		let mut unit = Unit::new();
+		let mut crc = Crc::new();
+
+		for w in pattern(100) {
+			unit.write_word(w);
+			crc.update_words(&[w]);
+			assert_eq!(unit.value(), crc.value());
+		}
+	}
+
+	#[test]
+	fn matches_unit_mixed() {
+		let mut unit = Unit::new();
+		unit.write_word(0x3433_3231);
+		unit.write_byte(b'5');
+		let mut crc = Crc::new();
+		crc.update_words(&[0x3433_3231]).update(b"5");
+		assert_eq!(unit.value(), crc.value());
+		assert_eq!(crc.value(), checksum(b"12345"));
+	}
+}
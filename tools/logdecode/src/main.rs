@@ -0,0 +1,477 @@
+use std::{env, fmt, fs, process};
+use std::io::{self, Read, BufReader, Write};
+
+const FRAME_HEADER: [u8; 2] = [0xa5, 0x5a];
+const FRAME_END: u8 = 0x00;
+const TASK_NONE: u8 = 0xff;
+const SECTION_NAME: &str = ".log_strings";
+
+/// Argument values. Warning: tags must be synchronized with `log::deferred` in the application
+///
+enum Arg {
+	U32(u32),  // Tag 1
+	I32(i32),  // Tag 2
+	U64(u64),  // Tag 3
+	I64(i64),  // Tag 4
+	Bool(bool),  // Tag 5
+	Char(char),  // Tag 6
+	Str(String),  // Tag 7
+}
+
+enum Error {
+	Io(io::Error),
+	Elf(&'static str),
+	Frame(&'static str),
+	Eof,
+}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Self {
+		match e.kind() {
+			io::ErrorKind::UnexpectedEof => Error::Eof,
+			_ => Error::Io(e),
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Io(e) => write!(f, "I/O error: {}", e),
+			Error::Elf(e) => write!(f, "ELF: {}", e),
+			Error::Frame(e) => write!(f, "malformed frame: {}", e),
+			Error::Eof => write!(f, "unexpected end of input"),
+		}
+	}
+}
+
+/// Contents of `.log_strings` section. A string's index in a frame is its offset in the section
+///
+struct Strings(Vec<u8>);
+
+impl Strings {
+	/// Extracts the section from a 32-bit little endian ELF
+	///
+	fn from_elf(elf: &[u8]) -> Result<Self, Error> {
+		let u16_at = |off: usize| elf.get(off..off + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+		let u32_at = |off: usize| elf.get(off..off + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+		if elf.get(0..4) != Some(b"\x7fELF") || elf.get(4) != Some(&1) || elf.get(5) != Some(&1) {
+			return Err(Error::Elf("not a 32-bit little endian ELF"));
+		}
+
+		let shoff = u32_at(0x20).ok_or(Error::Elf("truncated header"))?;
+		let shentsize = u16_at(0x2e).ok_or(Error::Elf("truncated header"))?;
+		let shnum = u16_at(0x30).ok_or(Error::Elf("truncated header"))?;
+		let shstrndx = u16_at(0x32).ok_or(Error::Elf("truncated header"))?;
+		let section = |i: usize| {
+			let base = shoff + i * shentsize;
+			// (name, offset, size)
+			Some((u32_at(base)?, u32_at(base + 16)?, u32_at(base + 20)?))
+		};
+		let (_, names_offset, _) = section(shstrndx).ok_or(Error::Elf("truncated section header"))?;
+
+		for i in 0..shnum {
+			let (name, offset, size) = section(i).ok_or(Error::Elf("truncated section header"))?;
+			let name = elf.get(names_offset + name..).ok_or(Error::Elf("bad section name"))?;
+			let name = &name[..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+
+			if name == SECTION_NAME.as_bytes() {
+				let data = elf.get(offset..offset + size).ok_or(Error::Elf("truncated section"))?;
+				return Ok(Self(data.to_vec()));
+			}
+		}
+
+		Err(Error::Elf("no `.log_strings` section, was the application built w/ `log-deferred`?"))
+	}
+
+	fn get(&self, index: usize) -> Option<&str> {
+		let s = self.0.get(index..)?;
+		let s = &s[..s.iter().position(|c| *c == 0)?];
+		std::str::from_utf8(s).ok()
+	}
+}
+
+fn read_u8(input: &mut impl Read) -> Result<u8, Error> {
+	let mut buf = [0u8; 1];
+	input.read_exact(&mut buf)?;
+	Ok(buf[0])
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> Result<[u8; N], Error> {
+	let mut buf = [0u8; N];
+	input.read_exact(&mut buf)?;
+	Ok(buf)
+}
+
+/// Skips bytes until a frame header is encountered
+///
+fn sync(input: &mut impl Read) -> Result<(), Error> {
+	let mut prev = read_u8(input)?;
+
+	loop {
+		let next = read_u8(input)?;
+
+		if [prev, next] == FRAME_HEADER {
+			return Ok(());
+		}
+
+		prev = next;
+	}
+}
+
+fn read_arg(input: &mut impl Read, tag: u8) -> Result<Arg, Error> {
+	Ok(match tag {
+		1 => Arg::U32(u32::from_le_bytes(read_array(input)?)),
+		2 => Arg::I32(i32::from_le_bytes(read_array(input)?)),
+		3 => Arg::U64(u64::from_le_bytes(read_array(input)?)),
+		4 => Arg::I64(i64::from_le_bytes(read_array(input)?)),
+		5 => Arg::Bool(read_u8(input)? != 0),
+		6 => Arg::Char(char::from_u32(u32::from_le_bytes(read_array(input)?)).unwrap_or(char::REPLACEMENT_CHARACTER)),
+		7 => {
+			let mut buf = vec![0u8; read_u8(input)? as usize];
+			input.read_exact(&mut buf)?;
+			Arg::Str(String::from_utf8_lossy(&buf).into_owned())
+		},
+		_ => return Err(Error::Frame("unknown argument tag")),
+	})
+}
+
+/// `{[:[#][0][width][type]]}` placeholder specification. Fill, alignment, and precision are not supported
+///
+#[derive(Default)]
+struct Spec {
+	alternate: bool,
+	zero: bool,
+	width: usize,
+	kind: char,  // 'x', 'X', 'b', 'o', '?', or ' ' (Display)
+}
+
+impl Spec {
+	fn parse(spec: &str) -> Self {
+		let mut ret = Spec {kind: ' ', ..Default::default()};
+		let mut chars = spec.chars().peekable();
+
+		if chars.peek() == Some(&'#') {
+			ret.alternate = true;
+			chars.next();
+		}
+
+		if chars.peek() == Some(&'0') {
+			ret.zero = true;
+			chars.next();
+		}
+
+		while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+			ret.width = ret.width * 10 + d as usize;
+			chars.next();
+		}
+
+		if let Some(c) = chars.next() {
+			ret.kind = c;  // "x?" and alike are treated as "x"
+		}
+
+		ret
+	}
+
+	fn format_int(&self, unsigned: u64, signed: Option<i64>) -> String {
+		let (prefix, digits) = match self.kind {
+			'x' => ("0x", format!("{:x}", unsigned)),
+			'X' => ("0x", format!("{:X}", unsigned)),
+			'b' => ("0b", format!("{:b}", unsigned)),
+			'o' => ("0o", format!("{:o}", unsigned)),
+			_ => ("", match signed {
+				Some(v) => v.to_string(),
+				None => unsigned.to_string(),
+			}),
+		};
+		let prefix = if self.alternate {prefix} else {""};
+		let (sign, digits) = match digits.strip_prefix('-') {
+			Some(d) => ("-", d.to_string()),
+			None => ("", digits),
+		};
+		let len = sign.len() + prefix.len() + digits.len();
+
+		if self.zero && len < self.width {
+			format!("{}{}{}{}", sign, prefix, "0".repeat(self.width - len), digits)
+		} else {
+			format!("{:>width$}", format!("{}{}{}", sign, prefix, digits), width = self.width)
+		}
+	}
+
+	fn format(&self, arg: &Arg) -> String {
+		match arg {
+			Arg::U32(v) => self.format_int(*v as u64, None),
+			Arg::I32(v) => self.format_int(*v as u32 as u64, Some(*v as i64)),
+			Arg::U64(v) => self.format_int(*v, None),
+			Arg::I64(v) => self.format_int(*v as u64, Some(*v)),
+			Arg::Bool(v) => format!("{:<width$}", v, width = self.width),
+			Arg::Char(v) if self.kind == '?' => format!("{:<width$?}", v, width = self.width),
+			Arg::Char(v) => format!("{:<width$}", v, width = self.width),
+			Arg::Str(v) if self.kind == '?' => format!("{:<width$?}", v, width = self.width),
+			Arg::Str(v) => format!("{:<width$}", v, width = self.width),
+		}
+	}
+}
+
+/// Substitutes arguments into a `core::fmt`-style format string
+///
+fn render(format: &str, args: &[Arg]) -> String {
+	let mut ret = String::new();
+	let mut args = args.iter();
+	let mut chars = format.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		match (c, chars.peek()) {
+			('{', Some('{')) | ('}', Some('}')) => {
+				ret.push(c);
+				chars.next();
+			},
+			('{', _) => {
+				let placeholder: String = chars.by_ref().take_while(|c| *c != '}').collect();
+				let spec = Spec::parse(placeholder.split_once(':').map(|(_, s)| s).unwrap_or(""));
+
+				match args.next() {
+					Some(arg) => ret.push_str(&spec.format(arg)),
+					None => ret.push_str("<missing>"),
+				}
+			},
+			_ => ret.push(c),
+		}
+	}
+
+	ret
+}
+
+fn level_tag(level: u8) -> &'static str {
+	match level {
+		1 => "E",
+		2 => "W",
+		3 => "I",
+		4 => "D",
+		5 => "T",
+		_ => "?",
+	}
+}
+
+fn decode_frame(input: &mut impl Read, strings: &Strings) -> Result<String, Error> {
+	let level = read_u8(input)?;
+	let tick = u32::from_le_bytes(read_array(input)?);
+	let task = match read_u8(input)? {
+		TASK_NONE => "-".to_string(),
+		id => id.to_string(),
+	};
+	let index = u16::from_le_bytes(read_array(input)?) as usize;
+	let format = strings.get(index).ok_or(Error::Frame("unknown string index"))?;
+	let mut args = Vec::new();
+
+	loop {
+		match read_u8(input)? {
+			FRAME_END => break,
+			tag => args.push(read_arg(input, tag)?),
+		}
+	}
+
+	Ok(format!("[{} {}] {}: {}", tick, task, level_tag(level), render(format, &args)))
+}
+
+fn run() -> Result<(), Error> {
+	let args: Vec<String> = env::args().collect();
+
+	if args.len() < 2 || args.len() > 3 {
+		eprintln!("Usage: {} <ELF> [INPUT]\n\nDecodes `log-deferred` records read from INPUT (a file, a configured \
+			serial device, or stdin if omitted)", args[0]);
+		process::exit(2);
+	}
+
+	let strings = Strings::from_elf(&fs::read(&args[1])?)?;
+	let mut input: Box<dyn Read> = match args.get(2) {
+		Some(path) => Box::new(BufReader::new(fs::File::open(path)?)),
+		None => Box::new(BufReader::new(io::stdin())),
+	};
+	let mut stdout = io::stdout();
+
+	loop {
+		sync(&mut input)?;
+
+		match decode_frame(&mut input, &strings) {
+			Ok(line) => writeln!(stdout, "{}", line)?,
+			Err(e @ Error::Frame(_)) => eprintln!("Skipping, {}", e),  // Resynchronize on the next header
+			Err(e) => return Err(e),
+		}
+
+		stdout.flush()?;
+	}
+}
+
+fn main() {
+	match run() {
+		Ok(_) | Err(Error::Eof) => {},
+		Err(e) => {
+			eprintln!("{}", e);
+			process::exit(1);
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Encodes frames the way `log::deferred` on the device does
+	///
+	struct Frame(Vec<u8>);
+
+	impl Frame {
+		fn new(level: u8, tick: u32, task: u8, string: u16) -> Self {
+			let mut bytes = FRAME_HEADER.to_vec();
+			bytes.push(level);
+			bytes.extend(tick.to_le_bytes());
+			bytes.push(task);
+			bytes.extend(string.to_le_bytes());
+
+			Frame(bytes)
+		}
+
+		fn arg(mut self, tag: u8, value: &[u8]) -> Self {
+			self.0.push(tag);
+			self.0.extend(value);
+			self
+		}
+
+		fn str(self, s: &str) -> Self {
+			let len = std::cmp::min(s.len(), u8::MAX as usize);
+			let mut value = vec![len as u8];
+			value.extend(&s.as_bytes()[..len]);
+			self.arg(7, &value)
+		}
+
+		fn end(mut self) -> Vec<u8> {
+			self.0.push(FRAME_END);
+			self.0
+		}
+	}
+
+	fn strings(formats: &[&str]) -> (Strings, Vec<u16>) {
+		let mut section = vec![0xffu8];  // A string's index is its offset, never 0 here
+		let mut indices = Vec::new();
+
+		for format in formats {
+			indices.push(section.len() as u16);
+			section.extend(format.as_bytes());
+			section.push(0);
+		}
+
+		(Strings(section), indices)
+	}
+
+	fn decode(bytes: &[u8], strings: &Strings) -> Result<String, Error> {
+		let mut input = bytes;
+		sync(&mut input)?;
+		decode_frame(&mut input, strings)
+	}
+
+	fn render_one(format: &str, arg: Arg) -> String {
+		render(format, &[arg])
+	}
+
+	#[test]
+	fn render_escapes_and_missing() {
+		assert_eq!(render("{{}} {}", &[Arg::U32(1)]), "{} 1");
+		assert_eq!(render("{} {}", &[Arg::U32(1)]), "1 <missing>");
+		assert_eq!(render("no args", &[]), "no args");
+	}
+
+	#[test]
+	fn render_specs() {
+		assert_eq!(render_one("{:x}", Arg::U32(0xbeef)), "beef");
+		assert_eq!(render_one("{:X}", Arg::U32(0xbeef)), "BEEF");
+		assert_eq!(render_one("{:#x}", Arg::U32(0xbeef)), "0xbeef");
+		assert_eq!(render_one("{:#010x}", Arg::U32(0xbeef)), "0x0000beef");
+		assert_eq!(render_one("{:08b}", Arg::U32(5)), "00000101");
+		assert_eq!(render_one("{:#o}", Arg::U32(8)), "0o10");
+		assert_eq!(render_one("{:5}", Arg::U32(42)), "   42");
+		assert_eq!(render_one("{:05}", Arg::I32(-42)), "-0042");
+		assert_eq!(render_one("{:x?}", Arg::U32(255)), "ff");
+		assert_eq!(render_one("{0:x}", Arg::U32(255)), "ff");
+	}
+
+	#[test]
+	fn render_arg_types() {
+		assert_eq!(render_one("{}", Arg::U32(u32::MAX)), "4294967295");
+		assert_eq!(render_one("{}", Arg::I32(i32::MIN)), "-2147483648");
+		assert_eq!(render_one("{:x}", Arg::I32(-1)), "ffffffff");
+		assert_eq!(render_one("{}", Arg::U64(u64::MAX)), "18446744073709551615");
+		assert_eq!(render_one("{}", Arg::I64(i64::MIN)), "-9223372036854775808");
+		assert_eq!(render_one("{:x}", Arg::I64(-1)), "ffffffffffffffff");
+		assert_eq!(render_one("{}", Arg::Bool(true)), "true");
+		assert_eq!(render_one("{:6}|", Arg::Bool(false)), "false |");
+		assert_eq!(render_one("{}", Arg::Char('λ')), "λ");
+		assert_eq!(render_one("{:?}", Arg::Char('\n')), "'\\n'");
+		assert_eq!(render_one("{}", Arg::Str("task".into())), "task");
+		assert_eq!(render_one("{:?}", Arg::Str("a\"b".into())), "\"a\\\"b\"");
+		assert_eq!(render_one("{:6}|", Arg::Str("ab".into())), "ab    |");
+	}
+
+	#[test]
+	fn round_trip() {
+		let (strings, idx) = strings(&["boot", "{} {} {} {} {} {} {}"]);
+		let frame = Frame::new(3, 1234, TASK_NONE, idx[0]).end();
+		assert_eq!(decode(&frame, &strings).ok().unwrap(), "[1234 -] I: boot");
+
+		let frame = Frame::new(1, u32::MAX, 2, idx[1])
+			.arg(1, &7u32.to_le_bytes())
+			.arg(2, &(-7i32).to_le_bytes())
+			.arg(3, &u64::MAX.to_le_bytes())
+			.arg(4, &(-1i64).to_le_bytes())
+			.arg(5, &[1])
+			.arg(6, &('ж' as u32).to_le_bytes())
+			.str("name")
+			.end();
+		assert_eq!(decode(&frame, &strings).ok().unwrap(),
+			"[4294967295 2] E: 7 -7 18446744073709551615 -1 true ж name");
+	}
+
+	#[test]
+	fn round_trip_long_str() {
+		let (strings, idx) = strings(&["{}"]);
+		let long = "x".repeat(300);
+		let frame = Frame::new(5, 0, 0, idx[0]).str(&long).end();
+		assert_eq!(decode(&frame, &strings).ok().unwrap(), format!("[0 0] T: {}", &long[..255]));
+	}
+
+	#[test]
+	fn sync_skips_garbage() {
+		let (strings, idx) = strings(&["ok"]);
+		let mut bytes = vec![0x00, 0xa5, 0x13, 0x5a];
+		bytes.extend(Frame::new(4, 1, 0, idx[0]).end());
+		assert_eq!(decode(&bytes, &strings).ok().unwrap(), "[1 0] D: ok");
+	}
+
+	#[test]
+	fn unknown_index() {
+		let (strings, _) = strings(&["ok"]);
+		let frame = Frame::new(3, 0, 0, 0x1000).end();
+		assert!(matches!(decode(&frame, &strings), Err(Error::Frame("unknown string index"))));
+	}
+
+	#[test]
+	fn unknown_tag() {
+		let (strings, idx) = strings(&["{}"]);
+		let frame = Frame::new(3, 0, 0, idx[0]).arg(8, &[0; 4]).end();
+		assert!(matches!(decode(&frame, &strings), Err(Error::Frame("unknown argument tag"))));
+	}
+
+	#[test]
+	fn truncated() {
+		let (strings, idx) = strings(&["{}"]);
+		let frame = Frame::new(3, 0, 0, idx[0]).arg(3, &1u64.to_le_bytes()).end();
+
+		for len in 2..frame.len() {
+			assert!(matches!(decode(&frame[..len], &strings), Err(Error::Eof)), "length {}", len);
+		}
+
+		let frame = Frame::new(3, 0, 0, idx[0]).str("abc").end();
+		assert!(matches!(decode(&frame[..frame.len() - 3], &strings), Err(Error::Eof)));
+	}
+}